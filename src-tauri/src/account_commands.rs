@@ -0,0 +1,166 @@
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use tauri::State;
+
+use crate::channel_commands::xtream_client::XtreamClient;
+use crate::db::DbConnection;
+use crate::models::{AccountStatus, Error};
+use crate::playlist_commands::load_playlist;
+
+const ACCOUNT_STATUS_COLUMNS: &str = "playlist_id, reachable, authenticated, http_status, status, message, exp_date, is_trial, active_cons, max_connections, allowed_output_formats, server_timezone, server_https_port, server_timestamp, error, checked_at";
+
+fn account_status_from_row(row: &Row) -> rusqlite::Result<AccountStatus> {
+    let formats: Option<String> = row.get(10)?;
+    Ok(AccountStatus {
+        playlist_id: row.get(0)?,
+        reachable: row.get(1)?,
+        authenticated: row.get(2)?,
+        http_status: row.get(3)?,
+        status: row.get(4)?,
+        message: row.get(5)?,
+        exp_date: row.get(6)?,
+        is_trial: row.get(7)?,
+        active_cons: row.get(8)?,
+        max_connections: row.get(9)?,
+        allowed_output_formats: formats
+            .map(|formats| {
+                formats
+                    .split(',')
+                    .filter(|format| !format.is_empty())
+                    .map(str::to_string)
+                    .collect()
+            })
+            .unwrap_or_default(),
+        server_timezone: row.get(11)?,
+        server_https_port: row.get(12)?,
+        server_timestamp: row.get(13)?,
+        error: row.get(14)?,
+        checked_at: row.get(15)?,
+        warnings: Vec::new(),
+    })
+}
+
+/// Returns the stored account snapshot of a playlist, if it was ever checked.
+pub fn load_account_status(
+    conn: &Connection,
+    playlist_id: i64,
+) -> Result<Option<AccountStatus>, Error> {
+    let status = conn
+        .query_row(
+            &format!(
+                "SELECT {} FROM account_status WHERE playlist_id = ?1",
+                ACCOUNT_STATUS_COLUMNS
+            ),
+            [playlist_id],
+            account_status_from_row,
+        )
+        .optional()?
+        .map(|mut status| {
+            status.update_warnings(chrono::Utc::now().timestamp());
+            status
+        });
+    Ok(status)
+}
+
+fn save_account_status(conn: &Connection, status: &AccountStatus) -> Result<(), Error> {
+    conn.execute(
+        &format!(
+            "INSERT OR REPLACE INTO account_status ({}) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16)",
+            ACCOUNT_STATUS_COLUMNS
+        ),
+        params![
+            status.playlist_id,
+            status.reachable,
+            status.authenticated,
+            status.http_status,
+            status.status,
+            status.message,
+            status.exp_date,
+            status.is_trial,
+            status.active_cons,
+            status.max_connections,
+            status.allowed_output_formats.join(","),
+            status.server_timezone,
+            status.server_https_port,
+            status.server_timestamp,
+            status.error,
+            status.checked_at,
+        ],
+    )?;
+    Ok(())
+}
+
+/// Calls `player_api.php` without an action and turns the answer into a
+/// snapshot that tells a dead server apart from rejected credentials.
+pub async fn probe_account(client: &XtreamClient, playlist_id: i64) -> AccountStatus {
+    let (http_status, result) = client.get_account_info_with_status().await;
+    let now = chrono::Utc::now();
+    let mut status = AccountStatus {
+        playlist_id,
+        http_status,
+        checked_at: now.to_rfc3339(),
+        ..Default::default()
+    };
+
+    match result {
+        Ok(info) => {
+            let user = info.user_info;
+            status.reachable = true;
+            status.authenticated = user.auth == Some(1);
+            status.status = user.status;
+            status.message = user.message;
+            status.exp_date = user.exp_date;
+            status.is_trial = user.is_trial.as_deref() == Some("1");
+            status.active_cons = user.active_cons;
+            status.max_connections = user.max_connections;
+            status.allowed_output_formats = user.allowed_output_formats;
+            if let Some(server) = info.server_info {
+                status.server_timezone = server.timezone;
+                status.server_https_port = server.https_port;
+                status.server_timestamp = server.timestamp_now;
+            }
+        }
+        Err(e) => {
+            // Panels answer bad credentials with 401/403 or with a body that
+            // is not an account object; anything else means no usable server.
+            status.reachable = matches!(http_status, Some(401) | Some(403))
+                || (http_status.is_some() && matches!(e, Error::Serde(_)));
+            status.error = Some(e.to_string());
+        }
+    }
+
+    status.update_warnings(now.timestamp());
+    status
+}
+
+/// Probes the provider account of a playlist and stores the snapshot.
+#[tauri::command(rename_all = "camelCase")]
+pub async fn check_account(
+    db: State<'_, DbConnection>,
+    playlist_id: i64,
+) -> Result<AccountStatus, Error> {
+    let playlist = {
+        let conn = db.0.lock().unwrap();
+        load_playlist(&conn, playlist_id)?
+    };
+    let client = XtreamClient::new(&playlist.server_url, &playlist.username, &playlist.password);
+
+    let status = probe_account(&client, playlist_id).await;
+    println!(
+        "Account check for playlist {}: reachable={}, authenticated={}, warnings={:?}",
+        playlist_id, status.reachable, status.authenticated, status.warnings
+    );
+
+    let conn = db.0.lock().unwrap();
+    save_account_status(&conn, &status)?;
+    Ok(status)
+}
+
+/// Returns the last stored account snapshot without contacting the provider.
+#[tauri::command(rename_all = "camelCase")]
+pub async fn get_account_status(
+    db: State<'_, DbConnection>,
+    playlist_id: i64,
+) -> Result<Option<AccountStatus>, Error> {
+    let conn = db.0.lock().unwrap();
+    load_account_status(&conn, playlist_id)
+}
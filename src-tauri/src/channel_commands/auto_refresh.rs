@@ -0,0 +1,115 @@
+use chrono::{DateTime, Duration, Utc};
+use std::collections::HashMap;
+use tauri::{AppHandle, Manager, Runtime};
+
+use crate::channel_commands::fetch_data::{import_source, record_import_result};
+use crate::channel_commands::import_registry::ImportRegistry;
+use crate::db::DbConnection;
+use crate::models::{Error, Playlist};
+use crate::playlist_commands::{playlist_from_row, PLAYLIST_COLUMNS};
+
+/// How often the scheduler looks for playlists that are due a refresh.
+const CHECK_INTERVAL: std::time::Duration = std::time::Duration::from_secs(60);
+
+/// Starts the background task that re-imports each active playlist once its
+/// `refresh_interval_minutes` has elapsed since the last successful import.
+pub fn start_auto_refresh<R: Runtime>(app_handle: AppHandle<R>) {
+    tauri::async_runtime::spawn(async move {
+        // Last attempt per playlist, so a failing provider is retried once per
+        // interval rather than on every check.
+        let mut last_attempts: HashMap<i64, DateTime<Utc>> = HashMap::new();
+        loop {
+            tokio::time::sleep(CHECK_INTERVAL).await;
+            if let Err(e) = refresh_due_playlists(&app_handle, &mut last_attempts) {
+                println!("Auto-refresh failed: {}", e);
+            }
+        }
+    });
+}
+
+fn is_due(playlist: &Playlist, last_attempt: Option<&DateTime<Utc>>, now: DateTime<Utc>) -> bool {
+    let interval = match playlist.refresh_interval_minutes {
+        Some(minutes) if minutes > 0 => Duration::minutes(minutes),
+        _ => return false,
+    };
+    let last_updated = playlist
+        .last_updated
+        .as_deref()
+        .and_then(|value| DateTime::parse_from_rfc3339(value).ok())
+        .map(|value| value.with_timezone(&Utc));
+    let last_run = match (last_updated, last_attempt.copied()) {
+        (Some(updated), Some(attempt)) => Some(updated.max(attempt)),
+        (updated, attempt) => updated.or(attempt),
+    };
+    match last_run {
+        Some(last_run) => now >= last_run + interval,
+        None => true,
+    }
+}
+
+fn load_active_playlists(db: &DbConnection) -> Result<Vec<Playlist>, Error> {
+    let conn = db.0.lock().unwrap();
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM playlists WHERE is_active = 1",
+        PLAYLIST_COLUMNS
+    ))?;
+    let playlists = stmt
+        .query_map([], playlist_from_row)?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(playlists)
+}
+
+/// Starts an import for every due playlist. Each runs in its own task so a
+/// slow or failing provider does not hold up the others or the next check.
+fn refresh_due_playlists<R: Runtime>(
+    app_handle: &AppHandle<R>,
+    last_attempts: &mut HashMap<i64, DateTime<Utc>>,
+) -> Result<(), Error> {
+    let db = app_handle.state::<DbConnection>();
+
+    let now = Utc::now();
+    let due: Vec<(i64, Playlist)> = load_active_playlists(&db)?
+        .into_iter()
+        .filter_map(|playlist| playlist.id.map(|id| (id, playlist)))
+        .filter(|(id, playlist)| is_due(playlist, last_attempts.get(id), now))
+        .collect();
+
+    for (playlist_id, playlist) in due {
+        last_attempts.insert(playlist_id, now);
+        let app_handle = app_handle.clone();
+        tauri::async_runtime::spawn(async move {
+            refresh_playlist(&app_handle, playlist_id, &playlist).await;
+        });
+    }
+
+    Ok(())
+}
+
+async fn refresh_playlist<R: Runtime>(
+    app_handle: &AppHandle<R>,
+    playlist_id: i64,
+    playlist: &Playlist,
+) {
+    let db = app_handle.state::<DbConnection>();
+    let imports = app_handle.state::<ImportRegistry>();
+    // A manual import, or the last refresh, of the playlist is still running.
+    let guard = match imports.start(playlist_id) {
+        Ok(guard) => guard,
+        Err(_) => return,
+    };
+    println!(
+        "Auto-refreshing playlist {} ({})",
+        playlist_id, playlist.name
+    );
+
+    let result = import_source(app_handle, &db, &guard.handle, playlist_id, playlist).await;
+    if let Err(e) = &result {
+        println!("Auto-refresh of playlist {} failed: {}", playlist_id, e);
+    }
+    if let Err(e) = record_import_result(&db, playlist_id, &result) {
+        println!(
+            "Could not record the auto-refresh of playlist {}: {}",
+            playlist_id, e
+        );
+    }
+}
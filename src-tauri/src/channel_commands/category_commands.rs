@@ -1,16 +1,32 @@
 use tauri::State;
 
-use crate::{db::DbConnection, models::Error};
+use crate::{db::DbConnection, models::Category, models::Error};
 
 #[tauri::command(rename_all = "camelCase")]
 pub async fn get_categories(
     db: State<'_, DbConnection>,
     playlist_id: i64,
-) -> Result<Vec<String>, Error> {
+    content_type: Option<String>,
+) -> Result<Vec<Category>, Error> {
     let conn = db.0.lock().unwrap();
-    let mut stmt = conn.prepare("SELECT DISTINCT category FROM channels WHERE playlist_id = ?")?;
+    let mut stmt = conn.prepare(
+        "SELECT id, playlist_id, category_id, name, content_type, parent_id, CAST(created_at AS TEXT)
+         FROM categories
+         WHERE playlist_id = ?1 AND (?2 IS NULL OR content_type = ?2)
+         ORDER BY content_type, name COLLATE NOCASE",
+    )?;
     let categories = stmt
-        .query_map([playlist_id], |row| row.get(0))?
+        .query_map(rusqlite::params![playlist_id, content_type], |row| {
+            Ok(Category {
+                id: row.get(0)?,
+                playlist_id: row.get(1)?,
+                category_id: row.get(2)?,
+                name: row.get(3)?,
+                content_type: row.get(4)?,
+                parent_id: row.get(5)?,
+                created_at: row.get(6)?,
+            })
+        })?
         .collect::<Result<Vec<_>, _>>()?;
     Ok(categories)
 }
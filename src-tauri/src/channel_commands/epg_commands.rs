@@ -0,0 +1,529 @@
+use flate2::read::MultiGzDecoder;
+use reqwest::header::CONTENT_ENCODING;
+use rusqlite::{params, Connection, Row};
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
+use tauri::State;
+use tokio::io::AsyncWriteExt;
+use url::Url;
+
+use crate::channel_commands::epg_mapping::match_guide_channels;
+use crate::channel_commands::import_registry::ImportRegistry;
+use crate::channel_commands::xmltv_parser::{parse_xmltv, XmltvItem};
+use crate::channel_commands::xtream_client::XtreamClient;
+use crate::db::DbConnection;
+use crate::models::{
+    EpgImportReport, EpgProgramme, EpgSource, EpgSourceResult, Error, Playlist, SourceType,
+    ValidationError,
+};
+use crate::playlist_commands::load_playlist;
+
+/// Guide items written per transaction. Each batch takes the database lock
+/// only briefly, so the UI stays responsive during large imports.
+const EPG_BATCH_SIZE: usize = 5000;
+
+/// First bytes of a gzip stream, which `.xml.gz` guides are served as
+/// whatever their URL or headers say.
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+/// EPG imports currently running, keyed by playlist ID. Kept apart from
+/// playlist imports so reading a guide does not block a refresh.
+#[derive(Default)]
+pub struct EpgImportRegistry(pub ImportRegistry);
+
+const EPG_SOURCE_COLUMNS: &str = "id, playlist_id, url, priority, enabled, created_at";
+
+/// Columns read by `programme_from_row`, in order.
+pub const EPG_PROGRAMME_COLUMNS: &str = "channel_id, start, end, title, sub_title, description, category, icon, episode_num, season, episode";
+
+pub fn programme_from_row(row: &Row) -> rusqlite::Result<EpgProgramme> {
+    Ok(EpgProgramme {
+        channel_id: row.get(0)?,
+        start: row.get(1)?,
+        end: row.get(2)?,
+        title: row.get(3)?,
+        sub_title: row.get(4)?,
+        description: row.get(5)?,
+        category: row.get(6)?,
+        icon: row.get(7)?,
+        episode_num: row.get(8)?,
+        season: row.get(9)?,
+        episode: row.get(10)?,
+    })
+}
+
+/// Where a guide is read from.
+enum GuideLocation {
+    Remote(Url),
+    Local(PathBuf),
+}
+
+impl GuideLocation {
+    /// The URL without its query, which carries the credentials of
+    /// `xmltv.php`, or the local path.
+    fn label(&self) -> String {
+        match self {
+            GuideLocation::Remote(url) => {
+                let mut url = url.clone();
+                url.set_query(None);
+                let _ = url.set_password(None);
+                url.to_string()
+            }
+            GuideLocation::Local(path) => path.display().to_string(),
+        }
+    }
+}
+
+fn local_guide(path: PathBuf) -> Result<GuideLocation, Error> {
+    if !path.is_file() {
+        return Err(ValidationError::FileNotFound(path.display().to_string()).into());
+    }
+    Ok(GuideLocation::Local(path))
+}
+
+/// Reads an EPG source: http(s) URLs are downloaded, `file://` URLs and
+/// plain paths are read from disk.
+fn parse_guide_location(input: &str) -> Result<GuideLocation, Error> {
+    let input = input.trim();
+    if input.is_empty() {
+        return Err(ValidationError::NoEpgSource.into());
+    }
+    match Url::parse(input) {
+        Ok(url) if url.scheme() == "http" || url.scheme() == "https" => {
+            Ok(GuideLocation::Remote(url))
+        }
+        Ok(url) if url.scheme() == "file" => {
+            let path = url
+                .to_file_path()
+                .map_err(|_| ValidationError::InvalidEpgSource(input.to_string()))?;
+            local_guide(path)
+        }
+        // A Windows drive letter parses as a one-letter scheme
+        Ok(url) if url.scheme().len() > 1 => {
+            Err(ValidationError::UnsupportedEpgScheme(url.scheme().to_string()).into())
+        }
+        _ => local_guide(PathBuf::from(input)),
+    }
+}
+
+/// The playlist's own guide: its `epg_url`, or for Xtream playlists the
+/// server's `xmltv.php`.
+fn default_guide(playlist: &Playlist) -> Result<Option<String>, Error> {
+    if let Some(epg_url) = playlist.epg_url.as_deref().map(str::trim) {
+        if !epg_url.is_empty() {
+            return Ok(Some(epg_url.to_string()));
+        }
+    }
+    match playlist.source_type.unwrap_or_default() {
+        SourceType::Xtream => {
+            let client =
+                XtreamClient::new(&playlist.server_url, &playlist.username, &playlist.password);
+            Ok(Some(client.xmltv_url()?.to_string()))
+        }
+        _ => Ok(None),
+    }
+}
+
+fn epg_source_from_row(row: &Row) -> rusqlite::Result<EpgSource> {
+    Ok(EpgSource {
+        id: row.get(0)?,
+        playlist_id: row.get(1)?,
+        url: row.get(2)?,
+        priority: row.get(3)?,
+        enabled: row.get(4)?,
+        created_at: row.get(5)?,
+    })
+}
+
+fn load_epg_sources(conn: &Connection, playlist_id: i64) -> Result<Vec<EpgSource>, Error> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM epg_sources WHERE playlist_id = ?1 ORDER BY priority, id",
+        EPG_SOURCE_COLUMNS
+    ))?;
+    let sources = stmt
+        .query_map([playlist_id], epg_source_from_row)?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(sources)
+}
+
+/// Streams the response body into `path` chunk by chunk, so the guide is
+/// never held in memory. Returns whether the server sent it gzip-encoded.
+async fn download_to_file(url: &Url, path: &Path) -> Result<bool, Error> {
+    let mut response = reqwest::get(url.clone()).await?.error_for_status()?;
+    let gzip_encoded = response
+        .headers()
+        .get(CONTENT_ENCODING)
+        .and_then(|value| value.to_str().ok())
+        .map(|value| value.to_ascii_lowercase().contains("gzip"))
+        .unwrap_or(false);
+
+    let mut file = tokio::fs::File::create(path)
+        .await
+        .map_err(|e| Error::Io(e.to_string()))?;
+    while let Some(chunk) = response.chunk().await? {
+        file.write_all(&chunk)
+            .await
+            .map_err(|e| Error::Io(e.to_string()))?;
+    }
+    file.flush().await.map_err(|e| Error::Io(e.to_string()))?;
+    Ok(gzip_encoded)
+}
+
+/// Opens a guide file, decompressing it on the fly when it is gzip.
+fn open_guide(path: &Path, gzip_encoded: bool) -> Result<Box<dyn BufRead>, Error> {
+    let file = File::open(path).map_err(|e| Error::Io(e.to_string()))?;
+    let mut reader = BufReader::with_capacity(1 << 16, file);
+    let is_gzip = reader
+        .fill_buf()
+        .map_err(|e| Error::Io(e.to_string()))?
+        .starts_with(&GZIP_MAGIC);
+    if is_gzip || gzip_encoded {
+        Ok(Box::new(BufReader::with_capacity(
+            1 << 16,
+            MultiGzDecoder::new(reader),
+        )))
+    } else {
+        Ok(Box::new(reader))
+    }
+}
+
+fn write_batch(
+    db: &DbConnection,
+    playlist_id: i64,
+    import_id: i64,
+    source: &str,
+    batch: &mut Vec<XmltvItem>,
+) -> Result<(), Error> {
+    let mut conn = db.0.lock().unwrap();
+    let tx = conn.transaction()?;
+    {
+        let mut insert_channel = tx.prepare(
+            "INSERT INTO epg_channels (playlist_id, channel_id, display_name, display_names, icon, import_id, source)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+             ON CONFLICT(playlist_id, channel_id) DO UPDATE SET
+                 display_name = excluded.display_name, display_names = excluded.display_names,
+                 icon = excluded.icon, import_id = excluded.import_id, source = excluded.source",
+        )?;
+        let mut insert_programme = tx.prepare(
+            "INSERT INTO epg_data (playlist_id, channel_id, start, end, title, sub_title, description, category, icon, episode_num, season, episode, import_id, origin)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, 'xmltv')
+             ON CONFLICT(playlist_id, channel_id, start) DO UPDATE SET
+                 end = excluded.end, title = excluded.title, sub_title = excluded.sub_title,
+                 description = excluded.description, category = excluded.category,
+                 icon = excluded.icon, episode_num = excluded.episode_num,
+                 season = excluded.season, episode = excluded.episode,
+                 import_id = excluded.import_id, origin = excluded.origin",
+        )?;
+
+        for item in batch.drain(..) {
+            match item {
+                XmltvItem::Channel(channel) => {
+                    insert_channel.execute(params![
+                        playlist_id,
+                        channel.id,
+                        channel.display_names.first(),
+                        serde_json::to_string(&channel.display_names)?,
+                        channel.icon,
+                        import_id,
+                        source,
+                    ])?;
+                }
+                XmltvItem::Programme(programme) => {
+                    insert_programme.execute(params![
+                        playlist_id,
+                        programme.channel_id,
+                        programme.start,
+                        programme.end,
+                        programme.title,
+                        programme.sub_title,
+                        programme.description,
+                        programme.category,
+                        programme.icon,
+                        programme.episode_num,
+                        programme.season,
+                        programme.episode,
+                        import_id,
+                    ])?;
+                }
+            }
+        }
+    }
+    tx.commit()?;
+    Ok(())
+}
+
+/// Deletes the playlist's guide rows that `import_id` did not write.
+fn remove_stale_epg(conn: &Connection, playlist_id: i64, import_id: i64) -> Result<usize, Error> {
+    conn.execute(
+        "DELETE FROM epg_channels WHERE playlist_id = ?1 AND import_id != ?2",
+        params![playlist_id, import_id],
+    )?;
+    // Listings cached from the short EPG are pruned by get_short_epg
+    Ok(conn.execute(
+        "DELETE FROM epg_data WHERE playlist_id = ?1 AND import_id != ?2 AND origin = 'xmltv'",
+        params![playlist_id, import_id],
+    )?)
+}
+
+/// Parses the XMLTV file at `path` into the playlist's guide in batches of
+/// `EPG_BATCH_SIZE` items. Channels in `claimed`, which guides of higher
+/// priority already provided, are left out; the ones this guide provides
+/// are added to it.
+fn import_guide_file(
+    db: &DbConnection,
+    playlist_id: i64,
+    import_id: i64,
+    path: &Path,
+    gzip_encoded: bool,
+    claimed: &mut HashSet<String>,
+    result: &mut EpgSourceResult,
+) -> Result<(), Error> {
+    let reader = open_guide(path, gzip_encoded)?;
+
+    let (mut channels, mut programmes) = (0, 0);
+    let mut provided: HashSet<String> = HashSet::new();
+    let mut overridden: HashSet<String> = HashSet::new();
+    let mut batch: Vec<XmltvItem> = Vec::with_capacity(EPG_BATCH_SIZE);
+    let parsed = parse_xmltv(reader, &mut |item| {
+        let channel_id = match &item {
+            XmltvItem::Channel(channel) => &channel.id,
+            XmltvItem::Programme(programme) => &programme.channel_id,
+        };
+        if claimed.contains(channel_id) {
+            if !overridden.contains(channel_id) {
+                overridden.insert(channel_id.clone());
+            }
+            return Ok(());
+        }
+        if !provided.contains(channel_id) {
+            provided.insert(channel_id.clone());
+        }
+
+        match &item {
+            XmltvItem::Channel(_) => channels += 1,
+            XmltvItem::Programme(_) => programmes += 1,
+        }
+        batch.push(item);
+        if batch.len() >= EPG_BATCH_SIZE {
+            write_batch(db, playlist_id, import_id, &result.source, &mut batch)?;
+        }
+        Ok(())
+    });
+    // Batches already written belong to this guide even if it broke off
+    claimed.extend(provided);
+    result.skipped = parsed?;
+    write_batch(db, playlist_id, import_id, &result.source, &mut batch)?;
+    result.channels = channels;
+    result.programmes = programmes;
+    result.overridden = overridden.len();
+    Ok(())
+}
+
+/// Reads one guide, downloading it to a temporary file first when remote.
+async fn import_guide(
+    db: &DbConnection,
+    playlist_id: i64,
+    import_id: i64,
+    input: &str,
+    claimed: &mut HashSet<String>,
+    result: &mut EpgSourceResult,
+) -> Result<(), Error> {
+    let location = parse_guide_location(input);
+    result.source = match &location {
+        Ok(location) => location.label(),
+        Err(_) => input.trim().to_string(),
+    };
+
+    // Parsing a large guide takes a while; keep it off the async workers
+    match location? {
+        GuideLocation::Local(path) => {
+            println!(
+                "Reading EPG for playlist {} from {}",
+                playlist_id, result.source
+            );
+            tokio::task::block_in_place(|| {
+                import_guide_file(db, playlist_id, import_id, &path, false, claimed, result)
+            })
+        }
+        GuideLocation::Remote(url) => {
+            println!(
+                "Downloading EPG for playlist {} from {}",
+                playlist_id, result.source
+            );
+            // Removed when dropped, whichever way the import ends
+            let file = tempfile::Builder::new()
+                .prefix("blipty-epg-")
+                .suffix(".xml")
+                .tempfile()
+                .map_err(|e| Error::Io(e.to_string()))?;
+            let path = file.path();
+            match download_to_file(&url, path).await {
+                Ok(gzip_encoded) => tokio::task::block_in_place(|| {
+                    import_guide_file(
+                        db,
+                        playlist_id,
+                        import_id,
+                        path,
+                        gzip_encoded,
+                        claimed,
+                        result,
+                    )
+                }),
+                Err(e) => Err(e),
+            }
+        }
+    }
+}
+
+/// Imports the playlist's XMLTV guides into `epg_channels` and `epg_data`:
+/// its enabled `epg_sources` by priority, then its own guide. When several
+/// list the same channel, the channel and its programmes come from the
+/// first. A guide that fails is reported and the others are still read;
+/// earlier programmes are then kept rather than pruned.
+/// Channels without a usable `epg_channel_id` are then matched by name.
+/// Fails with `Error::ImportInProgress` while the playlist's guide is
+/// already being imported.
+#[tauri::command(rename_all = "camelCase")]
+pub async fn import_epg(
+    db: State<'_, DbConnection>,
+    imports: State<'_, EpgImportRegistry>,
+    playlist_id: i64,
+) -> Result<EpgImportReport, Error> {
+    let _guard = imports.0.start(playlist_id)?;
+    let (playlist, sources) = {
+        let conn = db.0.lock().unwrap();
+        (
+            load_playlist(&conn, playlist_id)?,
+            load_epg_sources(&conn, playlist_id)?,
+        )
+    };
+    let mut inputs: Vec<String> = sources
+        .into_iter()
+        .filter(|source| source.enabled)
+        .map(|source| source.url)
+        .collect();
+    if let Some(guide) = default_guide(&playlist)? {
+        if !inputs.contains(&guide) {
+            inputs.push(guide);
+        }
+    }
+    if inputs.is_empty() {
+        return Err(ValidationError::NoEpgSource.into());
+    }
+
+    let import_id = chrono::Utc::now().timestamp_millis();
+    let mut report = EpgImportReport {
+        playlist_id,
+        ..Default::default()
+    };
+    let mut claimed: HashSet<String> = HashSet::new();
+    let mut last_error = None;
+    for input in &inputs {
+        let mut result = EpgSourceResult::default();
+        match import_guide(
+            &db,
+            playlist_id,
+            import_id,
+            input,
+            &mut claimed,
+            &mut result,
+        )
+        .await
+        {
+            Ok(()) => result.success = true,
+            Err(e) => {
+                println!("EPG source {} failed: {}", result.source, e);
+                result.error = Some(e.to_string());
+                last_error = Some(e);
+            }
+        }
+        report.channels += result.channels;
+        report.programmes += result.programmes;
+        report.skipped += result.skipped;
+        report.sources.push(result);
+    }
+
+    if !report.sources.iter().any(|source| source.success) {
+        if let Some(e) = last_error {
+            return Err(e);
+        }
+    }
+    // An empty guide is more likely a provider glitch than an empty lineup,
+    // and a guide that failed keeps what it provided last time
+    let complete = report.sources.iter().all(|source| source.success);
+    if report.programmes > 0 {
+        let mut conn = db.0.lock().unwrap();
+        if complete {
+            report.removed = remove_stale_epg(&conn, playlist_id, import_id)?;
+        }
+        report.matched = match_guide_channels(&mut conn, playlist_id)?.matched;
+    }
+
+    println!(
+        "EPG import of playlist {} finished: {} channels, {} programmes, {} skipped, {} removed",
+        playlist_id, report.channels, report.programmes, report.skipped, report.removed
+    );
+    Ok(report)
+}
+
+#[tauri::command(rename_all = "camelCase")]
+pub async fn get_epg_sources(
+    db: State<'_, DbConnection>,
+    playlist_id: i64,
+) -> Result<Vec<EpgSource>, Error> {
+    let conn = db.0.lock().unwrap();
+    load_epg_sources(&conn, playlist_id)
+}
+
+/// Adds a guide to the playlist. Without `priority` it is read after the
+/// playlist's other extra guides.
+#[tauri::command(rename_all = "camelCase")]
+pub async fn add_epg_source(
+    db: State<'_, DbConnection>,
+    playlist_id: i64,
+    url: String,
+    priority: Option<i64>,
+) -> Result<EpgSource, Error> {
+    let url = url.trim().to_string();
+    parse_guide_location(&url)?;
+
+    let conn = db.0.lock().unwrap();
+    conn.execute(
+        "INSERT INTO epg_sources (playlist_id, url, priority, enabled, created_at)
+         VALUES (?1, ?2, COALESCE(?3, (SELECT COALESCE(MAX(priority) + 1, 0) FROM epg_sources WHERE playlist_id = ?1)), 1, ?4)",
+        params![playlist_id, url, priority, chrono::Utc::now().to_rfc3339()],
+    )?;
+    Ok(conn.query_row(
+        &format!(
+            "SELECT {} FROM epg_sources WHERE id = ?1",
+            EPG_SOURCE_COLUMNS
+        ),
+        [conn.last_insert_rowid()],
+        epg_source_from_row,
+    )?)
+}
+
+#[tauri::command(rename_all = "camelCase")]
+pub async fn update_epg_source(
+    db: State<'_, DbConnection>,
+    source: EpgSource,
+) -> Result<(), Error> {
+    let url = source.url.trim();
+    parse_guide_location(url)?;
+
+    let conn = db.0.lock().unwrap();
+    conn.execute(
+        "UPDATE epg_sources SET url = ?1, priority = ?2, enabled = ?3 WHERE id = ?4",
+        params![url, source.priority, source.enabled, source.id],
+    )?;
+    Ok(())
+}
+
+#[tauri::command(rename_all = "camelCase")]
+pub async fn delete_epg_source(db: State<'_, DbConnection>, id: i64) -> Result<(), Error> {
+    let conn = db.0.lock().unwrap();
+    conn.execute("DELETE FROM epg_sources WHERE id = ?", [id])?;
+    Ok(())
+}
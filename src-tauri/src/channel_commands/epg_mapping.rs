@@ -0,0 +1,206 @@
+use chrono::Utc;
+use rusqlite::{params, Connection};
+use std::collections::HashSet;
+use tauri::State;
+
+use crate::channel_commands::epg_matcher::{GuideChannel, GuideMatch, GuideMatcher};
+use crate::db::DbConnection;
+use crate::models::{EpgMapping, EpgMatchReport, Error};
+
+fn load_guide_channels(conn: &Connection, playlist_id: i64) -> Result<Vec<GuideChannel>, Error> {
+    let mut stmt = conn.prepare(
+        "SELECT channel_id, display_name, display_names FROM epg_channels WHERE playlist_id = ?1",
+    )?;
+    let channels = stmt
+        .query_map([playlist_id], |row| {
+            let display_name: Option<String> = row.get(1)?;
+            let display_names: Option<String> = row.get(2)?;
+            let mut display_names: Vec<String> = display_names
+                .and_then(|names| serde_json::from_str(&names).ok())
+                .unwrap_or_default();
+            if display_names.is_empty() {
+                display_names.extend(display_name);
+            }
+            Ok(GuideChannel {
+                id: row.get(0)?,
+                display_names,
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(channels)
+}
+
+/// Links the playlist's live channels to guide channels by name where
+/// their own `epg_channel_id` is missing or not in the imported guide.
+/// Manual links are left alone; automatic ones are worked out again.
+pub fn match_guide_channels(
+    conn: &mut Connection,
+    playlist_id: i64,
+) -> Result<EpgMatchReport, Error> {
+    let mut report = EpgMatchReport {
+        playlist_id,
+        ..Default::default()
+    };
+    let guide = load_guide_channels(conn, playlist_id)?;
+    // Without a guide every link would be dropped until the next import
+    if guide.is_empty() {
+        return Ok(report);
+    }
+    let guide_ids: HashSet<&str> = guide.iter().map(|channel| channel.id.as_str()).collect();
+
+    let manual: HashSet<String> = conn
+        .prepare("SELECT stream_id FROM epg_channel_map WHERE playlist_id = ?1 AND manual = 1")?
+        .query_map([playlist_id], |row| row.get(0))?
+        .collect::<Result<_, _>>()?;
+    let channels: Vec<(String, String, Option<String>, Option<String>)> = conn
+        .prepare(
+            "SELECT stream_id, name, tvg_name, epg_channel_id FROM channels
+             WHERE playlist_id = ?1 AND COALESCE(content_type, 'live') = 'live'",
+        )?
+        .query_map([playlist_id], |row| {
+            Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?))
+        })?
+        .collect::<Result<_, _>>()?;
+
+    let matcher = GuideMatcher::new(&guide);
+    let updated_at = Utc::now().to_rfc3339();
+    let tx = conn.transaction()?;
+    {
+        let mut link = tx.prepare(
+            "INSERT INTO epg_channel_map (playlist_id, stream_id, epg_channel_id, manual, score, updated_at)
+             VALUES (?1, ?2, ?3, 0, ?4, ?5)
+             ON CONFLICT(playlist_id, stream_id) DO UPDATE SET
+                 epg_channel_id = excluded.epg_channel_id, score = excluded.score,
+                 updated_at = excluded.updated_at
+             WHERE manual = 0",
+        )?;
+        let mut unlink = tx.prepare(
+            "DELETE FROM epg_channel_map WHERE playlist_id = ?1 AND stream_id = ?2 AND manual = 0",
+        )?;
+
+        for (stream_id, name, tvg_name, epg_channel_id) in channels {
+            report.channels += 1;
+            if manual.contains(&stream_id) {
+                report.manual += 1;
+                continue;
+            }
+            let epg_channel_id = epg_channel_id.as_deref().map(str::trim).unwrap_or("");
+            if guide_ids.contains(epg_channel_id) {
+                report.linked += 1;
+                unlink.execute(params![playlist_id, stream_id])?;
+                continue;
+            }
+
+            // The M3U tvg-name is often closer to the guide than the title
+            let found = [Some(name.as_str()), tvg_name.as_deref()]
+                .into_iter()
+                .flatten()
+                .filter_map(|name| matcher.find(name))
+                .fold(None::<GuideMatch>, |best, found| match best {
+                    Some(best) if best.score >= found.score => Some(best),
+                    _ => Some(found),
+                });
+            match found {
+                Some(found) => {
+                    report.matched += 1;
+                    link.execute(params![
+                        playlist_id,
+                        stream_id,
+                        found.channel_id,
+                        found.score,
+                        updated_at,
+                    ])?;
+                }
+                None => {
+                    report.unmatched += 1;
+                    unlink.execute(params![playlist_id, stream_id])?;
+                }
+            }
+        }
+    }
+    // Channels the provider dropped
+    tx.execute(
+        "DELETE FROM epg_channel_map WHERE playlist_id = ?1 AND manual = 0
+         AND stream_id NOT IN (
+             SELECT stream_id FROM channels
+             WHERE playlist_id = ?1 AND COALESCE(content_type, 'live') = 'live'
+         )",
+        [playlist_id],
+    )?;
+    tx.commit()?;
+
+    println!(
+        "Matched EPG channels of playlist {}: {} linked, {} manual, {} matched, {} unmatched",
+        playlist_id, report.linked, report.manual, report.matched, report.unmatched
+    );
+    Ok(report)
+}
+
+/// Matches the playlist's channels to its imported guide by name. Runs
+/// after every `import_epg`; call it directly after changing channels.
+#[tauri::command(rename_all = "camelCase")]
+pub async fn match_epg_channels(
+    db: State<'_, DbConnection>,
+    playlist_id: i64,
+) -> Result<EpgMatchReport, Error> {
+    let mut conn = db.0.lock().unwrap();
+    match_guide_channels(&mut conn, playlist_id)
+}
+
+#[tauri::command(rename_all = "camelCase")]
+pub async fn get_epg_mappings(
+    db: State<'_, DbConnection>,
+    playlist_id: i64,
+) -> Result<Vec<EpgMapping>, Error> {
+    let conn = db.0.lock().unwrap();
+    let mut stmt = conn.prepare(
+        "SELECT playlist_id, stream_id, epg_channel_id, manual, score, updated_at
+         FROM epg_channel_map WHERE playlist_id = ?1 ORDER BY stream_id",
+    )?;
+    let mappings = stmt
+        .query_map([playlist_id], |row| {
+            Ok(EpgMapping {
+                playlist_id: row.get(0)?,
+                stream_id: row.get(1)?,
+                epg_channel_id: row.get(2)?,
+                manual: row.get(3)?,
+                score: row.get(4)?,
+                updated_at: row.get(5)?,
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(mappings)
+}
+
+/// Links a channel to a guide channel by hand. The link is kept through
+/// playlist refreshes and EPG imports until it is cleared with `None`,
+/// which hands the channel back to automatic matching.
+#[tauri::command(rename_all = "camelCase")]
+pub async fn set_epg_mapping(
+    db: State<'_, DbConnection>,
+    playlist_id: i64,
+    stream_id: String,
+    epg_channel_id: Option<String>,
+) -> Result<(), Error> {
+    let conn = db.0.lock().unwrap();
+    match epg_channel_id
+        .as_deref()
+        .map(str::trim)
+        .filter(|id| !id.is_empty())
+    {
+        Some(epg_channel_id) => {
+            conn.execute(
+                "INSERT OR REPLACE INTO epg_channel_map (playlist_id, stream_id, epg_channel_id, manual, score, updated_at)
+                 VALUES (?1, ?2, ?3, 1, NULL, ?4)",
+                params![playlist_id, stream_id, epg_channel_id, Utc::now().to_rfc3339()],
+            )?;
+        }
+        None => {
+            conn.execute(
+                "DELETE FROM epg_channel_map WHERE playlist_id = ?1 AND stream_id = ?2",
+                params![playlist_id, stream_id],
+            )?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,321 @@
+use std::collections::HashMap;
+
+/// Lowest `strsim::normalized_levenshtein` of two normalised names for
+/// them to count as the same channel.
+const MIN_FUZZY_SCORE: f64 = 0.85;
+
+/// Words that describe the feed rather than the channel.
+const QUALITY_WORDS: &[&str] = &[
+    "hd", "fhd", "uhd", "sd", "hq", "lq", "4k", "8k", "hevc", "h264", "h265", "1080p", "1080i",
+    "720p", "576p", "480p", "50fps", "60fps", "raw", "backup", "alt",
+];
+
+/// Country tags providers put before or after channel names, and XMLTV
+/// ids end with, e.g. `UK: BBC One`, `BBC One (UK)`, `BBCOne.uk`.
+const COUNTRY_CODES: &[&str] = &[
+    "uk", "gb", "us", "usa", "ca", "au", "nz", "ie", "de", "at", "ch", "fr", "be", "nl", "lu",
+    "es", "pt", "it", "pl", "tr", "in", "pk", "br", "mx", "se", "no", "dk", "fi", "gr", "ro", "ru",
+    "ua", "hr", "rs", "ba", "si", "cz", "sk", "hu", "bg", "il", "za", "ae", "sa", "eg", "ma",
+    "latino", "exyu",
+];
+
+const NUMBER_WORDS: &[(&str, &str)] = &[
+    ("one", "1"),
+    ("two", "2"),
+    ("three", "3"),
+    ("four", "4"),
+    ("five", "5"),
+    ("six", "6"),
+    ("seven", "7"),
+    ("eight", "8"),
+    ("nine", "9"),
+    ("ten", "10"),
+];
+
+/// A channel name reduced to what identifies the channel.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NormalizedName {
+    /// Lowercase letters and digits only, e.g. `bbc1` for `UK: BBC One HD`.
+    pub key: String,
+    /// The country tag that was stripped, if any.
+    pub country: Option<String>,
+}
+
+fn country_code(word: &str) -> Option<String> {
+    COUNTRY_CODES
+        .contains(&word)
+        .then(|| if word == "gb" { "uk" } else { word }.to_string())
+}
+
+/// Normalises a channel or guide name: lowercases it, drops a country tag
+/// such as `UK:` or `(UK)` or a trailing `UK`, quality words such as `HD`
+/// and punctuation, and spells numbers as digits.
+pub fn normalize_name(name: &str) -> NormalizedName {
+    let mut name = name.trim().to_lowercase();
+    let mut country = None;
+
+    // `UK: BBC One`, `UK | BBC One`, `UK - BBC One`
+    for separator in [":", "|", " - "] {
+        if let Some((prefix, rest)) = name.split_once(separator) {
+            let tag: String = prefix.chars().filter(|c| c.is_alphanumeric()).collect();
+            if let Some(code) = country_code(&tag) {
+                country = Some(code);
+                name = rest.to_string();
+                break;
+            }
+        }
+    }
+
+    // Bracketed parts are tags: `(UK)`, `[HD]`, `(Backup)`
+    let mut plain = String::with_capacity(name.len());
+    let mut tag = String::new();
+    let mut depth = 0;
+    for c in name.chars() {
+        match c {
+            '(' | '[' | '{' => depth += 1,
+            ')' | ']' | '}' if depth > 0 => {
+                depth -= 1;
+                let code: String = tag.chars().filter(|c| c.is_alphanumeric()).collect();
+                if let Some(code) = country_code(&code) {
+                    country.get_or_insert(code);
+                }
+                tag.clear();
+                plain.push(' ');
+            }
+            _ if depth > 0 => tag.push(c),
+            '&' => plain.push_str(" and "),
+            '+' => plain.push_str(" plus "),
+            _ => plain.push(c),
+        }
+    }
+
+    let mut words: Vec<&str> = plain
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty() && !QUALITY_WORDS.contains(word))
+        .collect();
+    // Keep a lone word: a channel may be called `Sky` or `DE`
+    while words.len() > 1 {
+        if let Some(code) = country_code(words[words.len() - 1]) {
+            country.get_or_insert(code);
+            words.pop();
+        } else if let Some(code) = country_code(words[0]) {
+            country.get_or_insert(code);
+            words.remove(0);
+        } else {
+            break;
+        }
+    }
+
+    let key = words
+        .into_iter()
+        .map(|word| {
+            NUMBER_WORDS
+                .iter()
+                .find(|(number, _)| *number == word)
+                .map_or(word, |(_, digits)| *digits)
+        })
+        .collect();
+    NormalizedName { key, country }
+}
+
+/// Splits an XMLTV id such as `BBCOne.uk` into a name and its country.
+fn split_guide_id(id: &str) -> (&str, Option<String>) {
+    match id.rsplit_once('.') {
+        Some((stem, suffix)) => (stem, country_code(&suffix.to_lowercase())),
+        None => (id, None),
+    }
+}
+
+fn digits(key: &str) -> String {
+    key.chars().filter(char::is_ascii_digit).collect()
+}
+
+/// A channel of an XMLTV guide.
+#[derive(Debug, Clone, Default)]
+pub struct GuideChannel {
+    pub id: String,
+    pub display_names: Vec<String>,
+}
+
+/// The guide channel a name matched and how closely, 1.0 being an exact
+/// match of the normalised names.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GuideMatch {
+    pub channel_id: String,
+    pub score: f64,
+}
+
+struct Candidate {
+    key: String,
+    index: usize,
+    country: Option<String>,
+}
+
+/// Finds the guide channel for a channel name, by normalised name first and
+/// by similarity second.
+pub struct GuideMatcher {
+    ids: Vec<String>,
+    exact: HashMap<String, Vec<usize>>,
+    /// Candidates by the first character of their key. Names that differ in
+    /// their first letter are never taken for the same channel.
+    by_initial: HashMap<char, Vec<Candidate>>,
+}
+
+impl GuideMatcher {
+    pub fn new(guide: &[GuideChannel]) -> Self {
+        let mut matcher = GuideMatcher {
+            ids: Vec::with_capacity(guide.len()),
+            exact: HashMap::new(),
+            by_initial: HashMap::new(),
+        };
+        for (index, channel) in guide.iter().enumerate() {
+            matcher.ids.push(channel.id.clone());
+            let (stem, id_country) = split_guide_id(&channel.id);
+            let names = channel
+                .display_names
+                .iter()
+                .map(String::as_str)
+                .chain(std::iter::once(stem));
+            for name in names {
+                let normalized = normalize_name(name);
+                let Some(initial) = normalized.key.chars().next() else {
+                    continue;
+                };
+                let exact = matcher.exact.entry(normalized.key.clone()).or_default();
+                if !exact.contains(&index) {
+                    exact.push(index);
+                }
+                matcher
+                    .by_initial
+                    .entry(initial)
+                    .or_default()
+                    .push(Candidate {
+                        key: normalized.key,
+                        index,
+                        country: normalized.country.or_else(|| id_country.clone()),
+                    });
+            }
+        }
+        matcher
+    }
+
+    fn country_of(&self, index: usize) -> Option<String> {
+        split_guide_id(&self.ids[index]).1
+    }
+
+    pub fn find(&self, name: &str) -> Option<GuideMatch> {
+        let name = normalize_name(name);
+        let initial = name.key.chars().next()?;
+
+        // Same name in several countries: prefer the channel's own
+        if let Some(indexes) = self.exact.get(&name.key) {
+            let index = indexes
+                .iter()
+                .copied()
+                .find(|&index| name.country.is_some() && self.country_of(index) == name.country)
+                .unwrap_or(indexes[0]);
+            return Some(GuideMatch {
+                channel_id: self.ids[index].clone(),
+                score: 1.0,
+            });
+        }
+
+        // `Sky Sports 1` and `Sky Sports 2` are close but different channels
+        let name_digits = digits(&name.key);
+        let name_len = name.key.chars().count() as f64;
+        let mut best: Option<(f64, bool, usize)> = None;
+        for candidate in self.by_initial.get(&initial)? {
+            let candidate_len = candidate.key.chars().count() as f64;
+            if (name_len - candidate_len).abs()
+                > (1.0 - MIN_FUZZY_SCORE) * name_len.max(candidate_len)
+                || digits(&candidate.key) != name_digits
+            {
+                continue;
+            }
+            let score = strsim::normalized_levenshtein(&name.key, &candidate.key);
+            if score < MIN_FUZZY_SCORE {
+                continue;
+            }
+            let same_country = name.country.is_some() && candidate.country == name.country;
+            let better = match best {
+                Some((best_score, best_country, _)) => {
+                    (score, same_country) > (best_score, best_country)
+                }
+                None => true,
+            };
+            if better {
+                best = Some((score, same_country, candidate.index));
+            }
+        }
+        best.map(|(score, _, index)| GuideMatch {
+            channel_id: self.ids[index].clone(),
+            score,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn guide(channels: &[(&str, &[&str])]) -> GuideMatcher {
+        let channels: Vec<GuideChannel> = channels
+            .iter()
+            .map(|(id, names)| GuideChannel {
+                id: id.to_string(),
+                display_names: names.iter().map(|name| name.to_string()).collect(),
+            })
+            .collect();
+        GuideMatcher::new(&channels)
+    }
+
+    #[test]
+    fn normalizes_tags_quality_and_numbers() {
+        for name in [
+            "UK: BBC One HD",
+            "BBC One (UK)",
+            "[UK] BBC 1",
+            "UK | BBC ONE FHD",
+            "bbc one uk",
+        ] {
+            let normalized = normalize_name(name);
+            assert_eq!(normalized.key, "bbc1", "{}", name);
+            assert_eq!(normalized.country.as_deref(), Some("uk"), "{}", name);
+        }
+        assert_eq!(normalize_name("Sky Sports F1 [Backup]").key, "skysportsf1");
+        assert_eq!(normalize_name("ITV +1").key, "itvplus1");
+        assert_eq!(normalize_name("DE").key, "de");
+    }
+
+    #[test]
+    fn matches_by_name_then_similarity() {
+        let matcher = guide(&[
+            ("BBCOne.uk", &["BBC One"]),
+            ("BBCOne.ie", &["BBC One"]),
+            ("Nickelodeon.uk", &["Nickelodeon"]),
+            ("SkySports1.uk", &["Sky Sports 1"]),
+            ("I123.schedulesdirect.org", &["Discovery Channel"]),
+        ]);
+
+        let found = matcher.find("UK: BBC 1 HD").unwrap();
+        assert_eq!(found.channel_id, "BBCOne.uk");
+        assert_eq!(found.score, 1.0);
+        assert_eq!(matcher.find("IE: BBC One").unwrap().channel_id, "BBCOne.ie");
+        assert_eq!(
+            matcher.find("SkySports1").unwrap().channel_id,
+            "SkySports1.uk"
+        );
+
+        let found = matcher.find("Nickelodean").unwrap();
+        assert_eq!(found.channel_id, "Nickelodeon.uk");
+        assert!(found.score < 1.0);
+        assert_eq!(
+            matcher.find("Discovery Chanel").unwrap().channel_id,
+            "I123.schedulesdirect.org"
+        );
+
+        assert_eq!(matcher.find("Sky Sports 2"), None);
+        assert_eq!(matcher.find("CNN"), None);
+        assert_eq!(matcher.find(""), None);
+    }
+}
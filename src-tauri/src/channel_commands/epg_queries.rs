@@ -0,0 +1,162 @@
+use chrono::Utc;
+use rusqlite::{params, Connection, OptionalExtension};
+use tauri::State;
+
+use crate::channel_commands::epg_commands::{programme_from_row, EPG_PROGRAMME_COLUMNS};
+use crate::db::DbConnection;
+use crate::models::{EpgGridChannel, Error, NowNext};
+
+/// The `epg_data.channel_id` of channel row `c`: its link in
+/// `epg_channel_map`, else its `epg_channel_id`, else its stream id, which
+/// `get_short_epg` caches listings under when there is neither.
+pub const GUIDE_CHANNEL_KEY: &str = "COALESCE(
+    (SELECT m.epg_channel_id FROM epg_channel_map m WHERE m.playlist_id = c.playlist_id AND m.stream_id = c.stream_id),
+    NULLIF(TRIM(c.epg_channel_id), ''),
+    c.stream_id)";
+
+/// Longest programme looked for before the start of a window. Bounding
+/// `start` on both sides keeps each lookup a range scan of the
+/// (playlist_id, channel_id, start) index of `epg_data`.
+const MAX_PROGRAMME_SECS: i64 = 24 * 60 * 60;
+
+fn load_now_next(
+    conn: &Connection,
+    playlist_id: i64,
+    channel_ids: &[i64],
+    now: i64,
+) -> Result<Vec<NowNext>, Error> {
+    let mut channel_stmt = conn.prepare(&format!(
+        "SELECT c.stream_id, {} FROM channels c WHERE c.playlist_id = ?1 AND c.id = ?2",
+        GUIDE_CHANNEL_KEY
+    ))?;
+    let mut current_stmt = conn.prepare(&format!(
+        "SELECT {} FROM epg_data
+         WHERE playlist_id = ?1 AND channel_id = ?2 AND start > ?3 AND start <= ?4 AND end > ?4
+         ORDER BY start DESC LIMIT 1",
+        EPG_PROGRAMME_COLUMNS
+    ))?;
+    let mut next_stmt = conn.prepare(&format!(
+        "SELECT {} FROM epg_data
+         WHERE playlist_id = ?1 AND channel_id = ?2 AND start > ?3
+         ORDER BY start LIMIT 1",
+        EPG_PROGRAMME_COLUMNS
+    ))?;
+
+    let mut now_next = Vec::with_capacity(channel_ids.len());
+    for &channel_id in channel_ids {
+        let channel: Option<(String, String)> = channel_stmt
+            .query_row(params![playlist_id, channel_id], |row| {
+                Ok((row.get(0)?, row.get(1)?))
+            })
+            .optional()?;
+        let Some((stream_id, guide_channel_id)) = channel else {
+            continue;
+        };
+
+        let current = current_stmt
+            .query_row(
+                params![playlist_id, guide_channel_id, now - MAX_PROGRAMME_SECS, now],
+                programme_from_row,
+            )
+            .optional()?;
+        let next = next_stmt
+            .query_row(
+                params![playlist_id, guide_channel_id, now],
+                programme_from_row,
+            )
+            .optional()?;
+        now_next.push(NowNext {
+            channel_id,
+            stream_id,
+            now: current,
+            next,
+        });
+    }
+    Ok(now_next)
+}
+
+fn load_epg_grid(
+    conn: &Connection,
+    playlist_id: i64,
+    category_id: Option<&str>,
+    from: i64,
+    to: i64,
+) -> Result<Vec<EpgGridChannel>, Error> {
+    let mut channel_stmt = conn.prepare(&format!(
+        "SELECT c.id, c.stream_id, c.name, c.num, c.stream_icon, c.epg_channel_id, {}
+         FROM channels c
+         WHERE c.playlist_id = ?1 AND COALESCE(c.content_type, 'live') = 'live'
+               AND (?2 IS NULL OR c.category_id = ?2)
+         ORDER BY c.id",
+        GUIDE_CHANNEL_KEY
+    ))?;
+    let channels = channel_stmt
+        .query_map(params![playlist_id, category_id], |row| {
+            Ok((
+                EpgGridChannel {
+                    channel_id: row.get(0)?,
+                    stream_id: row.get(1)?,
+                    name: row.get(2)?,
+                    num: row.get(3)?,
+                    stream_icon: row.get(4)?,
+                    epg_channel_id: row.get(5)?,
+                    programmes: Vec::new(),
+                },
+                row.get::<_, String>(6)?,
+            ))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut programme_stmt = conn.prepare(&format!(
+        "SELECT {} FROM epg_data
+         WHERE playlist_id = ?1 AND channel_id = ?2 AND start > ?3 AND start < ?5 AND end > ?4
+         ORDER BY start",
+        EPG_PROGRAMME_COLUMNS
+    ))?;
+    let mut grid = Vec::with_capacity(channels.len());
+    for (mut channel, guide_channel_id) in channels {
+        channel.programmes = programme_stmt
+            .query_map(
+                params![
+                    playlist_id,
+                    guide_channel_id,
+                    from - MAX_PROGRAMME_SECS,
+                    from,
+                    to
+                ],
+                programme_from_row,
+            )?
+            .collect::<Result<Vec<_>, _>>()?;
+        grid.push(channel);
+    }
+    Ok(grid)
+}
+
+/// Returns the programme airing now and the one after it for each of
+/// `channel_ids` (`channels.id`), in the order given. Ids that are not
+/// channels of the playlist are left out.
+#[tauri::command(rename_all = "camelCase")]
+pub async fn get_now_next(
+    db: State<'_, DbConnection>,
+    playlist_id: i64,
+    channel_ids: Vec<i64>,
+) -> Result<Vec<NowNext>, Error> {
+    let conn = db.0.lock().unwrap();
+    load_now_next(&conn, playlist_id, &channel_ids, Utc::now().timestamp())
+}
+
+/// Returns the playlist's live channels in `category` (a `category_id`, or
+/// all of them when `None`) with the programmes overlapping `from`..`to`,
+/// in Unix seconds. Channels without programmes are included so the grid
+/// shows every row.
+#[tauri::command(rename_all = "camelCase")]
+pub async fn get_epg_grid(
+    db: State<'_, DbConnection>,
+    playlist_id: i64,
+    category: Option<String>,
+    from: i64,
+    to: i64,
+) -> Result<Vec<EpgGridChannel>, Error> {
+    let conn = db.0.lock().unwrap();
+    load_epg_grid(&conn, playlist_id, category.as_deref(), from, to)
+}
@@ -0,0 +1,363 @@
+use rusqlite::{Connection, ToSql};
+use serde::Deserialize;
+use tauri::State;
+use url::Url;
+
+use crate::db::DbConnection;
+use crate::models::{Error, ValidationError};
+
+/// Which channels an export includes. Every filter that is set must match.
+#[derive(Debug, Default, Deserialize, Clone)]
+#[serde(default)]
+pub struct ExportFilter {
+    pub playlist_id: Option<i64>,
+    pub category_id: Option<String>,
+    pub content_type: Option<String>,
+    pub favorites_only: bool,
+    /// Case-insensitive substring of the channel name.
+    pub query: Option<String>,
+}
+
+/// A channel as written to the export.
+struct ExportEntry {
+    playlist_id: i64,
+    stream_id: String,
+    name: String,
+    stream_url: String,
+    epg_channel_id: Option<String>,
+    tvg_name: Option<String>,
+    stream_icon: Option<String>,
+    num: Option<String>,
+    category_name: Option<String>,
+    http_user_agent: Option<String>,
+    http_referrer: Option<String>,
+}
+
+fn load_entries(conn: &Connection, filter: &ExportFilter) -> Result<Vec<ExportEntry>, Error> {
+    let mut conditions: Vec<&str> = Vec::new();
+    let mut values: Vec<&dyn ToSql> = Vec::new();
+    if let Some(playlist_id) = &filter.playlist_id {
+        conditions.push("c.playlist_id = ?");
+        values.push(playlist_id);
+    }
+    if let Some(category_id) = &filter.category_id {
+        conditions.push("c.category_id = ?");
+        values.push(category_id);
+    }
+    if let Some(content_type) = &filter.content_type {
+        conditions.push("COALESCE(c.content_type, 'live') = ?");
+        values.push(content_type);
+    }
+    if filter.favorites_only {
+        conditions.push(
+            "EXISTS (SELECT 1 FROM favorites f WHERE f.playlist_id = c.playlist_id
+                     AND f.content_type = COALESCE(c.content_type, 'live') AND f.stream_id = c.stream_id)",
+        );
+    }
+    let pattern = filter
+        .query
+        .as_deref()
+        .map(str::trim)
+        .filter(|query| !query.is_empty())
+        .map(|query| {
+            let escaped = query
+                .replace('\\', "\\\\")
+                .replace('%', "\\%")
+                .replace('_', "\\_");
+            format!("%{}%", escaped)
+        });
+    if let Some(pattern) = &pattern {
+        conditions.push("c.name LIKE ? ESCAPE '\\'");
+        values.push(pattern);
+    }
+
+    let where_clause = if conditions.is_empty() {
+        String::new()
+    } else {
+        format!("WHERE {}", conditions.join(" AND "))
+    };
+    // Rows are in the order the provider listed them
+    let sql = format!(
+        "SELECT c.playlist_id, c.stream_id, c.name, c.stream_url, c.epg_channel_id, c.tvg_name,
+                c.stream_icon, c.num, c.category_name, c.http_user_agent, c.http_referrer
+         FROM channels c {} ORDER BY c.playlist_id, c.id",
+        where_clause
+    );
+
+    let mut stmt = conn.prepare(&sql)?;
+    let entries = stmt
+        .query_map(values.as_slice(), |row| {
+            Ok(ExportEntry {
+                playlist_id: row.get(0)?,
+                stream_id: row.get(1)?,
+                name: row.get(2)?,
+                stream_url: row.get(3)?,
+                epg_channel_id: row.get(4)?,
+                tvg_name: row.get(5)?,
+                stream_icon: row.get(6)?,
+                num: row.get(7)?,
+                category_name: row.get(8)?,
+                http_user_agent: row.get(9)?,
+                http_referrer: row.get(10)?,
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(entries)
+}
+
+/// Attribute values are double-quoted, so quotes and line breaks in
+/// provider data would end the value or the line early.
+fn clean(value: &str) -> String {
+    value
+        .replace('"', "'")
+        .replace(['\r', '\n'], " ")
+        .trim()
+        .to_string()
+}
+
+/// Checks the relay base URL and drops any trailing slash.
+fn relay_base(relay_url: &str) -> Result<String, ValidationError> {
+    let relay_url = relay_url.trim();
+    let invalid = || ValidationError::InvalidRelayUrl(relay_url.to_string());
+    let url = Url::parse(relay_url).map_err(|_| invalid())?;
+    if url.scheme() != "http" && url.scheme() != "https" {
+        return Err(invalid());
+    }
+    Ok(url.as_str().trim_end_matches('/').to_string())
+}
+
+/// URL of a stream behind the relay, `{relay}/{playlist_id}/{stream_id}`,
+/// which keeps the provider's credentials out of the file.
+fn relay_stream_url(relay: &str, entry: &ExportEntry) -> String {
+    let stream_id: String =
+        url::form_urlencoded::byte_serialize(entry.stream_id.as_bytes()).collect();
+    format!("{}/{}/{}", relay, entry.playlist_id, stream_id)
+}
+
+fn write_m3u(entries: &[ExportEntry], relay: Option<&str>) -> String {
+    let mut out = String::from("#EXTM3U\n");
+    for entry in entries {
+        let mut attributes = Vec::new();
+        let mut push = |key: &str, value: Option<&String>| {
+            if let Some(value) = value.map(|value| clean(value)).filter(|v| !v.is_empty()) {
+                attributes.push(format!(" {}=\"{}\"", key, value));
+            }
+        };
+        push("tvg-id", entry.epg_channel_id.as_ref());
+        push("tvg-name", entry.tvg_name.as_ref());
+        push("tvg-logo", entry.stream_icon.as_ref());
+        push("tvg-chno", entry.num.as_ref());
+        push("group-title", entry.category_name.as_ref());
+
+        out.push_str(&format!(
+            "#EXTINF:-1{},{}\n",
+            attributes.concat(),
+            entry.name.replace(['\r', '\n'], " ").trim()
+        ));
+
+        match relay {
+            Some(relay) => out.push_str(&relay_stream_url(relay, entry)),
+            None => {
+                // The relay sends these headers itself; direct players need them
+                if let Some(user_agent) = &entry.http_user_agent {
+                    out.push_str(&format!(
+                        "#EXTVLCOPT:http-user-agent={}\n",
+                        clean(user_agent)
+                    ));
+                }
+                if let Some(referrer) = &entry.http_referrer {
+                    out.push_str(&format!("#EXTVLCOPT:http-referrer={}\n", clean(referrer)));
+                }
+                out.push_str(&entry.stream_url);
+            }
+        }
+        out.push('\n');
+    }
+    out
+}
+
+/// Builds an extended M3U of the channels matching `filter`, writes it to
+/// `path` when given and returns the content. With `relay_url`, stream URLs
+/// point at the relay instead of the provider.
+#[tauri::command(rename_all = "camelCase")]
+pub async fn export_m3u(
+    db: State<'_, DbConnection>,
+    filter: ExportFilter,
+    path: Option<String>,
+    relay_url: Option<String>,
+) -> Result<String, Error> {
+    let relay = relay_url
+        .as_deref()
+        .filter(|relay_url| !relay_url.trim().is_empty())
+        .map(relay_base)
+        .transpose()?;
+
+    let content = {
+        let conn = db.0.lock().unwrap();
+        let entries = load_entries(&conn, &filter)?;
+        println!("Exporting {} channels to M3U", entries.len());
+        write_m3u(&entries, relay.as_deref())
+    };
+
+    if let Some(path) = path {
+        tokio::fs::write(&path, &content)
+            .await
+            .map_err(|e| Error::Io(e.to_string()))?;
+        println!("Wrote M3U export to {}", path);
+    }
+    Ok(content)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db;
+
+    fn open_db() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        db::init_db(&conn).unwrap();
+        db::check_and_create_channels_table(&conn).unwrap();
+        for migrate in [
+            db::migrate_db_v1,
+            db::migrate_db_v2,
+            db::migrate_db_v3,
+            db::migrate_db_v4,
+            db::migrate_db_v5,
+            db::migrate_db_v6,
+            db::migrate_db_v7,
+            db::migrate_db_v8,
+            db::migrate_db_v9,
+            db::migrate_db_v10,
+            db::migrate_db_v11,
+            db::migrate_db_v12,
+            db::migrate_db_v13,
+        ] {
+            migrate(&conn).unwrap();
+        }
+        for name in ["First", "Second"] {
+            conn.execute(
+                "INSERT INTO playlists (name, server_url, username, password, created_at)
+                 VALUES (?1, 'http://example.com', 'user', 'pass', 'now')",
+                [name],
+            )
+            .unwrap();
+        }
+        conn
+    }
+
+    fn add_channel(
+        conn: &Connection,
+        playlist_id: i64,
+        stream_id: &str,
+        name: &str,
+        category: &str,
+    ) {
+        conn.execute(
+            "INSERT INTO channels (playlist_id, stream_id, category_id, category_name, name,
+                                   stream_type, stream_url, created_at, content_type)
+             VALUES (?1, ?2, ?3, ?3, ?4, 'live', ?5, 'now', 'live')",
+            rusqlite::params![
+                playlist_id,
+                stream_id,
+                category,
+                name,
+                format!("http://example.com/live/user/pass/{}.ts", stream_id)
+            ],
+        )
+        .unwrap();
+    }
+
+    fn names(conn: &Connection, filter: ExportFilter) -> Vec<String> {
+        load_entries(conn, &filter)
+            .unwrap()
+            .into_iter()
+            .map(|entry| entry.name)
+            .collect()
+    }
+
+    #[test]
+    fn escapes_like_wildcards_in_the_query() {
+        let conn = open_db();
+        add_channel(&conn, 1, "1", "100% Sport", "sport");
+        add_channel(&conn, 1, "2", "1000 Sport", "sport");
+        add_channel(&conn, 1, "3", "News_24", "news");
+        add_channel(&conn, 1, "4", "News 24", "news");
+
+        let query = |query: &str| ExportFilter {
+            query: Some(query.to_string()),
+            ..Default::default()
+        };
+        assert_eq!(names(&conn, query("100%")), ["100% Sport"]);
+        assert_eq!(names(&conn, query("s_2")), ["News_24"]);
+        assert_eq!(names(&conn, query(" sport ")), ["100% Sport", "1000 Sport"]);
+    }
+
+    #[test]
+    fn filters_by_playlist_category_and_favorites() {
+        let conn = open_db();
+        add_channel(&conn, 1, "1", "One", "sport");
+        add_channel(&conn, 1, "2", "Two", "news");
+        add_channel(&conn, 2, "1", "Other One", "sport");
+        conn.execute(
+            "INSERT INTO favorites (playlist_id, stream_id, content_type) VALUES (1, '2', 'live')",
+            [],
+        )
+        .unwrap();
+
+        let playlist = ExportFilter {
+            playlist_id: Some(1),
+            ..Default::default()
+        };
+        assert_eq!(names(&conn, playlist.clone()), ["One", "Two"]);
+        let category = ExportFilter {
+            category_id: Some("sport".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(names(&conn, category), ["One", "Other One"]);
+        let favorites = ExportFilter {
+            favorites_only: true,
+            ..Default::default()
+        };
+        assert_eq!(names(&conn, favorites), ["Two"]);
+        let movies = ExportFilter {
+            content_type: Some("movie".to_string()),
+            ..playlist
+        };
+        assert!(names(&conn, movies).is_empty());
+    }
+
+    #[test]
+    fn keeps_credentials_out_of_relayed_exports() {
+        let conn = open_db();
+        add_channel(&conn, 2, "a b", "Spaced", "misc");
+        conn.execute(
+            "UPDATE channels SET http_user_agent = 'Agent/1', epg_channel_id = 'Spaced.uk'",
+            [],
+        )
+        .unwrap();
+        let entries = load_entries(&conn, &ExportFilter::default()).unwrap();
+
+        let relay = relay_base(" https://relay.local:8080/ ").unwrap();
+        assert_eq!(relay, "https://relay.local:8080");
+        let relayed = write_m3u(&entries, Some(&relay));
+        assert_eq!(
+            relayed,
+            "#EXTM3U\n#EXTINF:-1 tvg-id=\"Spaced.uk\" group-title=\"misc\",Spaced\n\
+             https://relay.local:8080/2/a+b\n"
+        );
+
+        let direct = write_m3u(&entries, None);
+        assert!(direct.contains("#EXTVLCOPT:http-user-agent=Agent/1\n"));
+        assert!(direct.contains("http://example.com/live/user/pass/a b.ts"));
+    }
+
+    #[test]
+    fn rejects_relay_urls_that_are_not_http() {
+        for relay_url in ["ftp://relay.local", "relay.local:8080", "not a url"] {
+            assert!(matches!(
+                relay_base(relay_url),
+                Err(ValidationError::InvalidRelayUrl(_))
+            ));
+        }
+    }
+}
@@ -1,444 +1,156 @@
-// For Android, we use a minimal reqwest client without native TLS
-#[cfg(target_os = "android")]
-use reqwest::{Client, ClientBuilder};
-
-// For other platforms, use the default reqwest
-#[cfg(not(target_os = "android"))]
-use reqwest;
-
-use serde::Deserialize;
-use tauri::State;
-
-use crate::db::DbConnection;
-
-// Rest of your original file follows
-use serde_json::Value;
-use std::collections::HashMap;
-
-use crate::channel_commands::extract_channels::extract_channels;
-use crate::models::Error;
-
-#[derive(Debug, Deserialize)]
-#[allow(dead_code)]
-pub struct ApiResponse<T> {
-    result: String,
-    data: T,
+use std::future::Future;
+
+use crate::channel_commands::xtream_client::XtreamClient;
+use crate::channel_commands::xtream_models::{LiveStream, Series, VodStream, XtreamCategory};
+use crate::models::{EndpointResult, Error};
+
+/// Everything `fetch_api_data` pulls from an Xtream server for an import.
+#[derive(Debug, Default)]
+pub struct ApiData {
+    pub live_categories: Vec<XtreamCategory>,
+    pub vod_categories: Vec<XtreamCategory>,
+    pub series_categories: Vec<XtreamCategory>,
+    pub live_streams: Vec<LiveStream>,
+    pub vod_streams: Vec<VodStream>,
+    pub series: Vec<Series>,
+    pub endpoints: Vec<EndpointResult>,
 }
 
-#[tauri::command]
-#[allow(dead_code)]
+#[tauri::command(rename_all = "camelCase")]
 pub async fn get_live_streams(
-    _db: State<'_, DbConnection>, // Renamed from db to _db
+    server_url: String,
     username: String,
     password: String,
-) -> Result<String, String> {
-    let url = format!(
-        "http://iptv.example.com/player_api.php?username={}&password={}&action=get_live_streams",
-        username, password
-    );
-
-    let response = reqwest::get(&url)
-        .await
-        .map_err(|e| e.to_string())?
-        .text()
+) -> Result<Vec<LiveStream>, Error> {
+    XtreamClient::new(&server_url, &username, &password)
+        .get_live_streams(None)
         .await
-        .map_err(|e| e.to_string())?;
-
-    Ok(response)
 }
 
-#[tauri::command]
-#[allow(dead_code)]
+#[tauri::command(rename_all = "camelCase")]
 pub async fn get_vod(
-    _db: State<'_, DbConnection>, // Renamed from db to _db
+    server_url: String,
     username: String,
     password: String,
-) -> Result<String, String> {
-    let url = format!(
-        "http://iptv.example.com/player_api.php?username={}&password={}&action=get_vod_streams",
-        username, password
-    );
-
-    let response = reqwest::get(&url)
-        .await
-        .map_err(|e| e.to_string())?
-        .text()
+) -> Result<Vec<VodStream>, Error> {
+    XtreamClient::new(&server_url, &username, &password)
+        .get_vod_streams(None)
         .await
-        .map_err(|e| e.to_string())?;
-
-    Ok(response)
 }
 
-#[tauri::command]
-#[allow(dead_code)]
+#[tauri::command(rename_all = "camelCase")]
 pub async fn get_series(
-    _db: State<'_, DbConnection>, // Renamed from db to _db
+    server_url: String,
     username: String,
     password: String,
-) -> Result<String, String> {
-    let url = format!(
-        "http://iptv.example.com/player_api.php?username={}&password={}&action=get_series",
-        username, password
-    );
-
-    let response = reqwest::get(&url)
-        .await
-        .map_err(|e| e.to_string())?
-        .text()
+) -> Result<Vec<Series>, Error> {
+    XtreamClient::new(&server_url, &username, &password)
+        .get_series(None)
         .await
-        .map_err(|e| e.to_string())?;
-
-    Ok(response)
 }
 
-pub async fn fetch_api_data(
-    server_url: String,
-    username: String,
-    password: String,
-) -> Result<
-    (
-        Value,
-        HashMap<String, String>,
-        HashMap<String, String>,
-        HashMap<String, String>,
-    ),
-    Error,
-> {
-    let mut _api_data = Value::Null;
-    let client = reqwest::Client::new();
-    let mut live_categories: HashMap<String, String> = HashMap::new();
-    let mut vod_categories: HashMap<String, String> = HashMap::new();
-    let mut series_categories: HashMap<String, String> = HashMap::new();
-    let mut all_channels: Vec<Value> = Vec::new();
-
-    // Fetch live categories
-    let live_categories_endpoint = format!(
-        "{}/player_api.php?username={}&password={}&action=get_live_categories",
-        server_url, username, password
-    );
-
-    println!(
-        "Trying live categories endpoint: {}",
-        live_categories_endpoint
-    );
-
-    match client.get(&live_categories_endpoint).send().await {
-        Ok(response) => {
-            if response.status().is_success() {
-                println!(
-                    "Successfully connected to live categories endpoint: {}",
-                    live_categories_endpoint
-                );
-
-                match response.json::<Value>().await {
-                    Ok(data) => {
-                        println!("Successfully parsed live categories JSON data");
-
-                        if let Some(categories_array) = data.as_array() {
-                            for category in categories_array {
-                                if let Some(category_id) =
-                                    category.get("category_id").and_then(|v| v.as_str())
-                                {
-                                    if let Some(category_name) =
-                                        category.get("category_name").and_then(|v| v.as_str())
-                                    {
-                                        live_categories.insert(
-                                            category_id.to_string(),
-                                            category_name.to_string(),
-                                        );
-                                    }
-                                }
-                            }
-                        }
-                    }
-                    Err(e) => {
-                        println!(
-                            "Failed to parse live categories JSON from {}: {}",
-                            live_categories_endpoint, e
-                        );
-                    }
-                }
-            } else {
-                println!(
-                    "Failed to connect to live categories endpoint {}: {}",
-                    live_categories_endpoint,
-                    response.status()
-                );
-            }
-        }
-        Err(e) => {
-            println!(
-                "Error connecting to live categories endpoint {}: {}",
-                live_categories_endpoint, e
-            );
-        }
-    }
-
-    // Fetch VOD categories
-    let vod_categories_endpoint = format!(
-        "{}/player_api.php?username={}&password={}&action=get_vod_categories",
-        server_url, username, password
-    );
-
-    println!(
-        "Trying VOD categories endpoint: {}",
-        vod_categories_endpoint
-    );
-
-    match client.get(&vod_categories_endpoint).send().await {
-        Ok(response) => {
-            if response.status().is_success() {
-                println!(
-                    "Successfully connected to VOD categories endpoint: {}",
-                    vod_categories_endpoint
-                );
-
-                match response.json::<Value>().await {
-                    Ok(data) => {
-                        println!("Successfully parsed VOD categories JSON data");
-
-                        if let Some(categories_array) = data.as_array() {
-                            for category in categories_array {
-                                if let Some(category_id) =
-                                    category.get("category_id").and_then(|v| v.as_str())
-                                {
-                                    if let Some(category_name) =
-                                        category.get("category_name").and_then(|v| v.as_str())
-                                    {
-                                        vod_categories.insert(
-                                            category_id.to_string(),
-                                            category_name.to_string(),
-                                        );
-                                    }
-                                }
-                            }
-                        }
-                    }
-                    Err(e) => {
-                        println!(
-                            "Failed to parse VOD categories JSON from {}: {}",
-                            vod_categories_endpoint, e
-                        );
-                    }
-                }
-            } else {
-                println!(
-                    "Failed to connect to VOD categories endpoint {}: {}",
-                    vod_categories_endpoint,
-                    response.status()
-                );
-            }
-        }
-        Err(e) => {
-            println!(
-                "Error connecting to VOD categories endpoint {}: {}",
-                vod_categories_endpoint, e
-            );
-        }
-    }
-
-    // Fetch series categories
-    let series_categories_endpoint = format!(
-        "{}/player_api.php?username={}&password={}&action=get_series_categories",
-        server_url, username, password
-    );
-
-    println!(
-        "Trying series categories endpoint: {}",
-        series_categories_endpoint
-    );
-
-    match client.get(&series_categories_endpoint).send().await {
-        Ok(response) => {
-            if response.status().is_success() {
-                println!(
-                    "Successfully connected to series categories endpoint: {}",
-                    series_categories_endpoint
-                );
-
-                match response.json::<Value>().await {
-                    Ok(data) => {
-                        println!("Successfully parsed series categories JSON data");
-
-                        if let Some(categories_array) = data.as_array() {
-                            for category in categories_array {
-                                if let Some(category_id) =
-                                    category.get("category_id").and_then(|v| v.as_str())
-                                {
-                                    if let Some(category_name) =
-                                        category.get("category_name").and_then(|v| v.as_str())
-                                    {
-                                        series_categories.insert(
-                                            category_id.to_string(),
-                                            category_name.to_string(),
-                                        );
-                                    }
-                                }
-                            }
-                        }
-                    }
-                    Err(e) => {
-                        println!(
-                            "Failed to parse series categories JSON from {}: {}",
-                            series_categories_endpoint, e
-                        );
-                    }
-                }
-            } else {
-                println!(
-                    "Failed to connect to series categories endpoint {}: {}",
-                    series_categories_endpoint,
-                    response.status()
-                );
-            }
+/// Awaits one of the client's list requests and records its outcome under
+/// the name of its `action`.
+async fn fetch_endpoint<T>(
+    action: &str,
+    request: impl Future<Output = (Option<u16>, Result<Vec<T>, Error>)>,
+    on_fetched: &(dyn Fn(&EndpointResult) + Sync),
+) -> (Vec<T>, EndpointResult) {
+    let (status, result) = request.await;
+
+    let (items, outcome) = match result {
+        Ok(items) => {
+            println!("Successfully fetched {} ({} items)", action, items.len());
+            let outcome = EndpointResult {
+                endpoint: action.to_string(),
+                success: true,
+                status,
+                item_count: items.len(),
+                error: None,
+            };
+            (items, outcome)
         }
         Err(e) => {
-            println!(
-                "Error connecting to series categories endpoint {}: {}",
-                series_categories_endpoint, e
-            );
+            println!("Failed to fetch {}: {}", action, e);
+            let outcome = EndpointResult {
+                endpoint: action.to_string(),
+                success: false,
+                status,
+                item_count: 0,
+                error: Some(e.to_string()),
+            };
+            (Vec::new(), outcome)
         }
-    }
-
-    // Fetch live streams
-    let live_streams_endpoint = format!(
-        "{}/player_api.php?username={}&password={}&action=get_live_streams",
-        server_url, username, password
-    );
+    };
 
-    println!("Trying live streams endpoint: {}", live_streams_endpoint);
-
-    match client.get(&live_streams_endpoint).send().await {
-        Ok(response) => {
-            if response.status().is_success() {
-                println!(
-                    "Successfully connected to live streams endpoint: {}",
-                    live_streams_endpoint
-                );
-
-                match response.json::<Value>().await {
-                    Ok(data) => {
-                        println!("Successfully parsed live streams JSON data");
-
-                        let live_channels = extract_channels(&data, "live".to_string());
-                        all_channels.extend(live_channels);
-                    }
-                    Err(e) => {
-                        println!("Failed to parse JSON from {}: {}", live_streams_endpoint, e);
-                    }
-                }
-            } else {
-                println!(
-                    "Failed to connect to live streams endpoint {}: {}",
-                    live_streams_endpoint,
-                    response.status()
-                );
-            }
-        }
-        Err(e) => {
-            println!(
-                "Error connecting to live streams endpoint {}: {}",
-                live_streams_endpoint, e
-            );
-        }
-    }
-
-    // Fetch VOD streams
-    let vod_streams_endpoint = format!(
-        "{}/player_api.php?username={}&password={}&action=get_vod_streams",
-        server_url, username, password
-    );
-
-    println!("Trying VOD streams endpoint: {}", vod_streams_endpoint);
-
-    match client.get(&vod_streams_endpoint).send().await {
-        Ok(response) => {
-            if response.status().is_success() {
-                println!(
-                    "Successfully connected to VOD streams endpoint: {}",
-                    vod_streams_endpoint
-                );
-
-                match response.json::<Value>().await {
-                    Ok(data) => {
-                        println!("Successfully parsed VOD streams JSON data");
-
-                        let vod_channels = extract_channels(&data, "vod".to_string());
-                        all_channels.extend(vod_channels);
-                    }
-                    Err(e) => {
-                        println!("Failed to parse JSON from {}: {}", vod_streams_endpoint, e);
-                    }
-                }
-            } else {
-                println!(
-                    "Failed to connect to VOD streams endpoint {}: {}",
-                    vod_streams_endpoint,
-                    response.status()
-                );
-            }
-        }
-        Err(e) => {
-            println!(
-                "Error connecting to VOD streams endpoint {}: {}",
-                vod_streams_endpoint, e
-            );
-        }
-    }
+    on_fetched(&outcome);
+    (items, outcome)
+}
 
-    // Fetch series streams
-    let series_streams_endpoint = format!(
-        "{}/player_api.php?username={}&password={}&action=get_series",
-        server_url, username, password
-    );
+/// Number of endpoints requested by `fetch_api_data`.
+pub const ENDPOINT_COUNT: usize = 6;
 
-    println!(
-        "Trying series streams endpoint: {}",
-        series_streams_endpoint
+/// Fetches all categories and streams concurrently. The client limits how many
+/// of these requests are actually in flight against the provider at once.
+/// `on_fetched` is called as each endpoint completes.
+pub async fn fetch_api_data(
+    client: &XtreamClient,
+    on_fetched: &(dyn Fn(&EndpointResult) + Sync),
+) -> Result<ApiData, Error> {
+    let (
+        (live_categories, live_categories_result),
+        (vod_categories, vod_categories_result),
+        (series_categories, series_categories_result),
+        (live_streams, live_streams_result),
+        (vod_streams, vod_streams_result),
+        (series, series_result),
+    ) = tokio::join!(
+        fetch_endpoint(
+            "get_live_categories",
+            client.get_live_categories_with_status(),
+            on_fetched,
+        ),
+        fetch_endpoint(
+            "get_vod_categories",
+            client.get_vod_categories_with_status(),
+            on_fetched,
+        ),
+        fetch_endpoint(
+            "get_series_categories",
+            client.get_series_categories_with_status(),
+            on_fetched,
+        ),
+        fetch_endpoint(
+            "get_live_streams",
+            client.get_live_streams_with_status(None),
+            on_fetched,
+        ),
+        fetch_endpoint(
+            "get_vod_streams",
+            client.get_vod_streams_with_status(None),
+            on_fetched,
+        ),
+        fetch_endpoint(
+            "get_series",
+            client.get_series_with_status(None),
+            on_fetched
+        ),
     );
 
-    match client.get(&series_streams_endpoint).send().await {
-        Ok(response) => {
-            if response.status().is_success() {
-                println!(
-                    "Successfully connected to series streams endpoint: {}",
-                    series_streams_endpoint
-                );
-
-                match response.json::<Value>().await {
-                    Ok(data) => {
-                        println!("Successfully parsed series streams JSON data");
-
-                        let series_channels = extract_channels(&data, "series".to_string());
-                        all_channels.extend(series_channels);
-                    }
-                    Err(e) => {
-                        println!(
-                            "Failed to parse JSON from {}: {}",
-                            series_streams_endpoint, e
-                        );
-                    }
-                }
-            } else {
-                println!(
-                    "Failed to connect to series streams endpoint {}: {}",
-                    series_streams_endpoint,
-                    response.status()
-                );
-            }
-        }
-        Err(e) => {
-            println!(
-                "Error connecting to series streams endpoint {}: {}",
-                series_streams_endpoint, e
-            );
-        }
-    }
-
-    Ok((
-        Value::Array(all_channels),
+    Ok(ApiData {
         live_categories,
         vod_categories,
         series_categories,
-    ))
+        live_streams,
+        vod_streams,
+        series,
+        endpoints: vec![
+            live_categories_result,
+            vod_categories_result,
+            series_categories_result,
+            live_streams_result,
+            vod_streams_result,
+            series_result,
+        ],
+    })
 }
@@ -1,122 +1,493 @@
-use serde_json::Value;
-use std::collections::HashMap;
-use tauri::{AppHandle, Runtime, State};
-
-use crate::channel_commands::extract_channels::extract_channels;
-use crate::channel_commands::fetch_api::fetch_api_data;
-use crate::channel_commands::insert_categories::insert_categories;
-use crate::channel_commands::insert_channels::insert_channels;
+use rusqlite::params;
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use tauri::{AppHandle, Emitter, Runtime, State};
+
+use crate::account_commands::load_account_status;
+use crate::channel_commands::fetch_api::{fetch_api_data, ApiData, ENDPOINT_COUNT};
+use crate::channel_commands::import_registry::{ImportHandle, ImportRegistry};
+use crate::channel_commands::insert_categories::{
+    insert_categories, remove_missing_categories, CategoryMap,
+};
+use crate::channel_commands::insert_channels::{
+    channel_key, insert_channels, remove_missing_channels, ChannelKey,
+};
+use crate::channel_commands::process_m3u::import_m3u_content;
+use crate::channel_commands::xtream_client::{live_output_format, XtreamClient};
+use crate::channel_commands::xtream_models::XtreamCategory;
+use crate::models::{
+    Channel, EndpointResult, ImportPhase, ImportProgress, ImportReport, ImportStatus, Playlist,
+    SourceType,
+};
+use crate::playlist_commands::load_playlist;
 use crate::{db::DbConnection, models::Error};
 
-#[tauri::command(rename_all = "camelCase")]
-pub async fn fetch_and_populate_data<R: Runtime>(
-    _app_handle: AppHandle<R>,
-    db: State<'_, DbConnection>,
+fn add_categories(
+    all_categories: &mut CategoryMap,
+    categories: Vec<XtreamCategory>,
+    content_type: &str,
+) {
+    for category in categories {
+        all_categories.insert(
+            (content_type.to_string(), category.category_id),
+            (category.category_name, category.parent_id),
+        );
+    }
+}
+
+/// Stream endpoints and the content type of the channels they list.
+const STREAM_ENDPOINTS: [(&str, &str); 3] = [
+    ("get_live_streams", "live"),
+    ("get_vod_streams", "movie"),
+    ("get_series", "series"),
+];
+
+/// Category endpoints and the content type of the categories they list.
+const CATEGORY_ENDPOINTS: [(&str, &str); 3] = [
+    ("get_live_categories", "live"),
+    ("get_vod_categories", "movie"),
+    ("get_series_categories", "series"),
+];
+
+/// Event emitted with an `ImportProgress` payload during `fetch_and_populate_data`.
+pub const IMPORT_PROGRESS_EVENT: &str = "import-progress";
+
+fn emit_progress<R: Runtime>(app_handle: &AppHandle<R>, progress: ImportProgress) {
+    if let Err(e) = app_handle.emit(IMPORT_PROGRESS_EVENT, progress) {
+        println!("Failed to emit import progress: {}", e);
+    }
+}
+
+/// Content types of `endpoints` whose list arrived with items in it, which
+/// are the only ones safe to prune. A failed endpoint would look like the
+/// provider dropped the whole list, and an empty one is more likely a
+/// provider glitch than an empty lineup.
+fn complete_types(report: &ImportReport, endpoints: &[(&str, &'static str)]) -> Vec<&'static str> {
+    endpoints
+        .iter()
+        .filter(|(endpoint, _)| {
+            report
+                .endpoints
+                .iter()
+                .any(|e| e.endpoint == *endpoint && e.success && e.item_count > 0)
+        })
+        .map(|(_, content_type)| *content_type)
+        .collect()
+}
+
+/// Writes the fetched data in a single transaction. Nothing is committed if
+/// the import is cancelled or any statement fails.
+fn write_api_data<R: Runtime>(
+    app_handle: &AppHandle<R>,
+    db: &DbConnection,
+    handle: &ImportHandle,
+    client: &XtreamClient,
     playlist_id: i64,
-    server_url: String,
-    username: String,
-    password: String,
+    api_data: ApiData,
+    report: &mut ImportReport,
 ) -> Result<(), Error> {
-    let (api_data, live_categories, vod_categories, series_categories) =
-        fetch_api_data(server_url.clone(), username.clone(), password.clone()).await?;
-
-    // For M3U format, handle differently
-    if let Value::String(m3u_content) = &api_data {
-        println!("Received M3U content, processing...");
-        // Process M3U content and populate channels table directly
-        // Call the process_m3u_content function directly since it's in the same module
-        crate::channel_commands::process_m3u::process_m3u_content(
-            db.clone(),
+    // Combine live, VOD and series categories for insertion
+    let mut all_categories = CategoryMap::new();
+    add_categories(&mut all_categories, api_data.live_categories, "live");
+    add_categories(&mut all_categories, api_data.vod_categories, "movie");
+    add_categories(&mut all_categories, api_data.series_categories, "series");
+
+    let with_url = |mut channel: Channel| {
+        channel.stream_url = client.stream_url(&channel);
+        channel
+    };
+    let live_channels: Vec<Channel> = api_data
+        .live_streams
+        .into_iter()
+        .map(|stream| with_url(stream.into_channel(playlist_id)))
+        .collect();
+    let vod_channels: Vec<Channel> = api_data
+        .vod_streams
+        .into_iter()
+        .map(|stream| with_url(stream.into_channel(playlist_id)))
+        .collect();
+    let series_channels: Vec<Channel> = api_data
+        .series
+        .into_iter()
+        .map(|series| with_url(series.into_channel(playlist_id)))
+        .collect();
+
+    let mut conn = db.0.lock().unwrap();
+    let tx = conn.transaction()?;
+
+    insert_categories(&tx, playlist_id, &all_categories)?;
+    let category_types = complete_types(report, &CATEGORY_ENDPOINTS);
+    remove_missing_categories(&tx, playlist_id, &category_types, &all_categories)?;
+    handle.check()?;
+    emit_progress(
+        app_handle,
+        ImportProgress {
             playlist_id,
-            &m3u_content,
-            &server_url,
-            &username,
-            &password,
-        )
-        .await?;
-        return Ok(());
-    }
-    // Prepare live categories for insertion
-    let mut live_categories_with_type: HashMap<String, (String, String, Option<i64>)> =
-        HashMap::new();
-    for (category_id, category_name) in live_categories {
-        live_categories_with_type.insert(
-            category_id.clone(),
-            (category_name, "live".to_string(), None),
-        );
-    }
+            phase: ImportPhase::CategoriesInserted,
+            current: all_categories.len(),
+            total: all_categories.len(),
+            endpoint: None,
+        },
+    );
 
-    // Prepare VOD categories for insertion
-    let mut vod_categories_with_type: HashMap<String, (String, String, Option<i64>)> =
-        HashMap::new();
-    for (category_id, category_name) in vod_categories {
-        vod_categories_with_type.insert(
-            category_id.clone(),
-            (category_name, "movie".to_string(), None),
-        );
+    let total_channels = live_channels.len() + vod_channels.len() + series_channels.len();
+    let mut written = 0;
+    for channels in [&live_channels, &vod_channels, &series_channels] {
+        let stats = insert_channels(
+            &tx,
+            channels,
+            &all_categories,
+            playlist_id,
+            &mut |current| {
+                handle.check()?;
+                emit_progress(
+                    app_handle,
+                    ImportProgress {
+                        playlist_id,
+                        phase: ImportPhase::ChannelsWritten,
+                        current: written + current,
+                        total: total_channels,
+                        endpoint: None,
+                    },
+                );
+                Ok(())
+            },
+        )?;
+        written += channels.len();
+        report.inserted += stats.inserted;
+        report.updated += stats.updated;
+        report.unchanged += stats.unchanged;
+        report.skipped += stats.skipped;
     }
 
-    // Prepare series categories for insertion
-    let mut series_categories_with_type: HashMap<String, (String, String, Option<i64>)> =
-        HashMap::new();
-    for (category_id, category_name) in series_categories {
-        series_categories_with_type.insert(
-            category_id.clone(),
-            (category_name, "series".to_string(), None),
+    let stream_types = complete_types(report, &STREAM_ENDPOINTS);
+    if stream_types.len() < STREAM_ENDPOINTS.len() {
+        println!(
+            "Only removing {:?} channels; the other stream lists failed or were empty",
+            stream_types
         );
     }
+    let keep: HashSet<ChannelKey> = live_channels
+        .iter()
+        .chain(&vod_channels)
+        .chain(&series_channels)
+        .map(channel_key)
+        .collect();
+    report.removed = remove_missing_channels(&tx, playlist_id, &stream_types, &keep)?;
+
+    handle.check()?;
+    tx.commit()?;
+    Ok(())
+}
 
-    // Combine categories
-    let mut all_categories: HashMap<String, (String, String, Option<i64>)> = HashMap::new();
-    all_categories.extend(live_categories_with_type);
-    all_categories.extend(vod_categories_with_type);
-    all_categories.extend(series_categories_with_type);
+/// Fetches a playlist from its Xtream server and writes it to the database.
+/// Returns `Error::Cancelled`, with nothing written, if `handle` is cancelled.
+pub async fn import_playlist<R: Runtime>(
+    app_handle: &AppHandle<R>,
+    db: &DbConnection,
+    handle: &ImportHandle,
+    playlist_id: i64,
+    client: &XtreamClient,
+) -> Result<ImportReport, Error> {
+    let fetched = AtomicUsize::new(0);
+    let on_fetched = |outcome: &EndpointResult| {
+        emit_progress(
+            app_handle,
+            ImportProgress {
+                playlist_id,
+                phase: ImportPhase::EndpointFetched,
+                current: fetched.fetch_add(1, Ordering::SeqCst) + 1,
+                total: ENDPOINT_COUNT,
+                endpoint: Some(outcome.endpoint.clone()),
+            },
+        )
+    };
 
-    insert_categories(db.clone(), &all_categories)?;
+    let api_data = tokio::select! {
+        api_data = fetch_api_data(client, &on_fetched) => api_data?,
+        _ = handle.cancelled() => return Err(Error::Cancelled),
+    };
 
-    // Extract live channels
-    let all_channels = extract_channels(&api_data, "live".to_string());
+    let mut report = ImportReport {
+        playlist_id,
+        endpoints: api_data.endpoints.clone(),
+        categories: api_data.live_categories.len()
+            + api_data.vod_categories.len()
+            + api_data.series_categories.len(),
+        live: api_data.live_streams.len(),
+        vod: api_data.vod_streams.len(),
+        series: api_data.series.len(),
+        ..Default::default()
+    };
 
-    insert_channels(
-        db.clone(),
-        &all_channels,
-        &all_categories,
-        &server_url,
-        &username,
-        &password,
+    write_api_data(
+        app_handle,
+        db,
+        handle,
+        client,
         playlist_id,
-        &"live".to_string(),
+        api_data,
+        &mut report,
     )?;
 
-    // Extract vod channels
-    let vod_channels = extract_channels(&api_data, "vod".to_string());
+    report.finish();
+    println!(
+        "Import of playlist {} finished with status {:?}: {} inserted, {} updated, {} unchanged, {} removed, {} skipped",
+        playlist_id,
+        report.status,
+        report.inserted,
+        report.updated,
+        report.unchanged,
+        report.removed,
+        report.skipped
+    );
 
-    insert_channels(
-        db.clone(),
-        &vod_channels,
-        &all_categories,
-        &server_url,
-        &username,
-        &password,
+    Ok(report)
+}
+
+/// Stores the outcome of an import on the playlist row: `last_updated` when
+/// something was imported, and `last_error` describing any failure.
+pub fn record_import_result(
+    db: &DbConnection,
+    playlist_id: i64,
+    result: &Result<ImportReport, Error>,
+) -> Result<(), Error> {
+    let (succeeded, error) = match result {
+        Ok(report) => {
+            let failures: Vec<String> = report
+                .endpoints
+                .iter()
+                .filter(|e| !e.success)
+                .map(|e| {
+                    format!(
+                        "{}: {}",
+                        e.endpoint,
+                        e.error.as_deref().unwrap_or("unknown error")
+                    )
+                })
+                .collect();
+            let error = (!failures.is_empty()).then(|| failures.join("; "));
+            (report.status != ImportStatus::Failed, error)
+        }
+        Err(Error::Cancelled) => return Ok(()),
+        Err(e) => (false, Some(e.to_string())),
+    };
+
+    let conn = db.0.lock().unwrap();
+    if succeeded {
+        conn.execute(
+            "UPDATE playlists SET last_updated = ?1, last_error = ?2 WHERE id = ?3",
+            params![chrono::Utc::now().to_rfc3339(), error, playlist_id],
+        )?;
+    } else {
+        conn.execute(
+            "UPDATE playlists SET last_error = ?1 WHERE id = ?2",
+            params![error, playlist_id],
+        )?;
+    }
+    Ok(())
+}
+
+/// Syncs a playlist with M3U content, reporting progress like an Xtream import.
+fn write_m3u<R: Runtime>(
+    app_handle: &AppHandle<R>,
+    db: &DbConnection,
+    handle: &ImportHandle,
+    playlist_id: i64,
+    endpoint: &str,
+    status: Option<u16>,
+    content: &str,
+) -> Result<ImportReport, Error> {
+    handle.check()?;
+    import_m3u_content(
+        db,
         playlist_id,
-        &"vod".to_string(),
-    )?;
+        endpoint,
+        status,
+        content,
+        &mut |current, total| {
+            handle.check()?;
+            emit_progress(
+                app_handle,
+                ImportProgress {
+                    playlist_id,
+                    phase: ImportPhase::ChannelsWritten,
+                    current,
+                    total,
+                    endpoint: None,
+                },
+            );
+            Ok(())
+        },
+    )
+}
 
-    // Extract series channels
-    let series_channels = extract_channels(&api_data, "series".to_string());
+/// Downloads a plain M3U playlist.
+async fn download_m3u(url: &str) -> (Option<u16>, Result<String, Error>) {
+    println!("Downloading M3U playlist from {}", url);
+    let response = match reqwest::get(url).await {
+        Ok(response) => response,
+        Err(e) => return (None, Err(e.into())),
+    };
+    let status = response.status().as_u16();
+    let result = match response.error_for_status() {
+        Ok(response) => response.text().await.map_err(Error::from),
+        Err(e) => Err(e.into()),
+    };
+    (Some(status), result)
+}
 
-    insert_channels(
-        db.clone(),
-        &series_channels,
-        &all_categories,
-        &server_url,
-        &username,
-        &password,
+/// Report for an M3U source that could not be read.
+fn failed_source(
+    playlist_id: i64,
+    endpoint: &str,
+    status: Option<u16>,
+    error: Error,
+) -> ImportReport {
+    let mut report = ImportReport {
         playlist_id,
-        &"series".to_string(),
-    )?;
+        endpoints: vec![EndpointResult {
+            endpoint: endpoint.to_string(),
+            success: false,
+            status,
+            item_count: 0,
+            error: Some(error.to_string()),
+        }],
+        ..Default::default()
+    };
+    report.finish();
+    report
+}
 
-    println!("Successfully fetched and populated data");
+/// Imports a playlist from wherever its `source_type` says its channels live.
+/// Xtream servers whose `player_api.php` fails entirely are retried through
+/// their `get.php` M3U export; the report then lists both, so a working
+/// fallback ends up `Partial`.
+pub async fn import_source<R: Runtime>(
+    app_handle: &AppHandle<R>,
+    db: &DbConnection,
+    handle: &ImportHandle,
+    playlist_id: i64,
+    playlist: &Playlist,
+) -> Result<ImportReport, Error> {
+    match playlist.source_type.unwrap_or_default() {
+        SourceType::Xtream => {
+            let allowed_formats = {
+                let conn = db.0.lock().unwrap();
+                load_account_status(&conn, playlist_id)?
+                    .map(|status| status.allowed_output_formats)
+                    .unwrap_or_default()
+            };
+            let live_format =
+                live_output_format(playlist.output_format.as_deref(), &allowed_formats);
+            let client =
+                XtreamClient::new(&playlist.server_url, &playlist.username, &playlist.password)
+                    .with_live_format(live_format);
+            let mut report = import_playlist(app_handle, db, handle, playlist_id, &client).await?;
+            if report.status != ImportStatus::Failed {
+                return Ok(report);
+            }
 
-    Ok(())
+            println!(
+                "player_api.php failed, trying get.php for playlist {}",
+                playlist_id
+            );
+            let (status, content) = tokio::select! {
+                result = client.get_m3u(Some(live_format)) => result,
+                _ = handle.cancelled() => return Err(Error::Cancelled),
+            };
+            match content {
+                Ok(content) => {
+                    let mut fallback = write_m3u(
+                        app_handle,
+                        db,
+                        handle,
+                        playlist_id,
+                        "get.php",
+                        status,
+                        &content,
+                    )?;
+                    // Keep the API failures so last_error shows it is broken
+                    fallback.endpoints.splice(0..0, report.endpoints);
+                    fallback.finish();
+                    Ok(fallback)
+                }
+                Err(e) => {
+                    println!("get.php failed for playlist {}: {}", playlist_id, e);
+                    report
+                        .endpoints
+                        .extend(failed_source(playlist_id, "get.php", status, e).endpoints);
+                    Ok(report)
+                }
+            }
+        }
+        SourceType::M3uUrl => {
+            let (status, content) = tokio::select! {
+                result = download_m3u(&playlist.server_url) => result,
+                _ = handle.cancelled() => return Err(Error::Cancelled),
+            };
+            match content {
+                Ok(content) => write_m3u(
+                    app_handle,
+                    db,
+                    handle,
+                    playlist_id,
+                    "m3u_url",
+                    status,
+                    &content,
+                ),
+                Err(e) => Ok(failed_source(playlist_id, "m3u_url", status, e)),
+            }
+        }
+        SourceType::M3uFile => match tokio::fs::read(&playlist.server_url).await {
+            Ok(bytes) => {
+                let content = String::from_utf8_lossy(&bytes);
+                write_m3u(
+                    app_handle,
+                    db,
+                    handle,
+                    playlist_id,
+                    "m3u_file",
+                    None,
+                    &content,
+                )
+            }
+            Err(e) => Ok(failed_source(
+                playlist_id,
+                "m3u_file",
+                None,
+                Error::Io(e.to_string()),
+            )),
+        },
+    }
+}
+
+#[tauri::command(rename_all = "camelCase")]
+pub async fn fetch_and_populate_data<R: Runtime>(
+    app_handle: AppHandle<R>,
+    db: State<'_, DbConnection>,
+    imports: State<'_, ImportRegistry>,
+    playlist_id: i64,
+) -> Result<ImportReport, Error> {
+    let playlist = {
+        let conn = db.0.lock().unwrap();
+        load_playlist(&conn, playlist_id)?
+    };
+    let guard = imports.start(playlist_id)?;
+
+    let result = import_source(&app_handle, &db, &guard.handle, playlist_id, &playlist).await;
+    if let Err(Error::Cancelled) = result {
+        println!("Import of playlist {} was cancelled", playlist_id);
+    }
+    record_import_result(&db, playlist_id, &result)?;
+    result
+}
+
+/// Cancels the running import of a playlist. Returns false if none is running.
+#[tauri::command(rename_all = "camelCase")]
+pub async fn cancel_import(
+    imports: State<'_, ImportRegistry>,
+    playlist_id: i64,
+) -> Result<bool, Error> {
+    Ok(imports.cancel(playlist_id))
 }
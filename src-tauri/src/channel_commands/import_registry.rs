@@ -0,0 +1,90 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::sync::Notify;
+
+use crate::models::Error;
+
+/// Cancellation flag shared between a running import and `cancel_import`.
+#[derive(Clone, Default)]
+pub struct ImportHandle {
+    cancelled: Arc<AtomicBool>,
+    notify: Arc<Notify>,
+}
+
+impl ImportHandle {
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+        self.notify.notify_waiters();
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// Returns `Error::Cancelled` once the import has been cancelled.
+    pub fn check(&self) -> Result<(), Error> {
+        if self.is_cancelled() {
+            Err(Error::Cancelled)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Resolves when the import is cancelled.
+    pub async fn cancelled(&self) {
+        loop {
+            let notified = self.notify.notified();
+            if self.is_cancelled() {
+                return;
+            }
+            notified.await;
+        }
+    }
+}
+
+/// Imports currently running, keyed by playlist ID.
+#[derive(Default)]
+pub struct ImportRegistry(Mutex<HashMap<i64, ImportHandle>>);
+
+impl ImportRegistry {
+    /// Registers an import for `playlist_id`, failing if one is already running.
+    /// The registration is removed when the returned guard is dropped.
+    pub fn start(&self, playlist_id: i64) -> Result<ImportGuard<'_>, Error> {
+        let mut imports = self.0.lock().unwrap();
+        if imports.contains_key(&playlist_id) {
+            return Err(Error::ImportInProgress(playlist_id));
+        }
+
+        let handle = ImportHandle::default();
+        imports.insert(playlist_id, handle.clone());
+        Ok(ImportGuard {
+            registry: self,
+            playlist_id,
+            handle,
+        })
+    }
+
+    /// Cancels the running import of `playlist_id`. Returns false if none is running.
+    pub fn cancel(&self, playlist_id: i64) -> bool {
+        match self.0.lock().unwrap().get(&playlist_id) {
+            Some(handle) => {
+                handle.cancel();
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+pub struct ImportGuard<'a> {
+    registry: &'a ImportRegistry,
+    playlist_id: i64,
+    pub handle: ImportHandle,
+}
+
+impl Drop for ImportGuard<'_> {
+    fn drop(&mut self) {
+        self.registry.0.lock().unwrap().remove(&self.playlist_id);
+    }
+}
@@ -1,34 +1,67 @@
-use rusqlite::params;
+use rusqlite::{params, Connection};
 use std::collections::HashMap;
-use tauri::State;
 
-use crate::{db::DbConnection, models::Error};
+use crate::models::Error;
 
+/// An import's categories keyed by `(content_type, category_id)`, since
+/// providers reuse the same IDs across live, VOD and series, mapped to their
+/// name and parent ID.
+pub type CategoryMap = HashMap<(String, String), (String, Option<i64>)>;
+
+/// Inserts or renames the playlist's categories using `tx`; the caller owns
+/// the transaction.
 pub fn insert_categories(
-    db: State<'_, DbConnection>,
-    all_categories: &HashMap<String, (String, String, Option<i64>)>,
+    tx: &Connection,
+    playlist_id: i64,
+    all_categories: &CategoryMap,
 ) -> Result<(), Error> {
     // Insert categories into the database
     println!(
         "Inserting {} categories into the database",
         all_categories.len()
     );
-    let mut conn = db.0.lock().unwrap();
-    let tx = conn.transaction()?;
-    for (cat_id, (cat_name, content_type, parent_id)) in all_categories {
-        let result = tx.execute(
-            "INSERT INTO categories (category_id, name, content_type, parent_id, created_at, updated_at) VALUES (?1, ?2, ?3, ?4, strftime('%s', 'now'), strftime('%s', 'now'))",
-            params![cat_id, cat_name, content_type, parent_id],
-        );
-        match result {
-            Ok(_) => {
-                println!("Inserted category: {}", cat_name);
-            }
-            Err(e) => {
-                println!("Error inserting category {}: {}", cat_name, e);
-            }
-        }
+    for ((content_type, cat_id), (cat_name, parent_id)) in all_categories {
+        tx.execute(
+            "INSERT INTO categories (playlist_id, category_id, name, content_type, parent_id, created_at, updated_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, strftime('%s', 'now'), strftime('%s', 'now'))
+             ON CONFLICT(playlist_id, content_type, category_id)
+             DO UPDATE SET name = excluded.name, parent_id = excluded.parent_id, updated_at = excluded.updated_at
+             WHERE name IS NOT excluded.name OR parent_id IS NOT excluded.parent_id",
+            params![playlist_id, cat_id, cat_name, content_type, parent_id],
+        )?;
     }
-    tx.commit()?;
     Ok(())
 }
+
+/// Deletes the playlist's categories of `content_types` that are not in
+/// `keep`. Returns the number of rows removed.
+pub fn remove_missing_categories(
+    tx: &Connection,
+    playlist_id: i64,
+    content_types: &[&str],
+    keep: &CategoryMap,
+) -> Result<usize, Error> {
+    let mut stmt =
+        tx.prepare("SELECT id, category_id, content_type FROM categories WHERE playlist_id = ?1")?;
+    let stored = stmt
+        .query_map([playlist_id], |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, String>(2)?,
+            ))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut removed = 0;
+    for (id, category_id, content_type) in stored {
+        if !content_types.contains(&content_type.as_str()) {
+            continue;
+        }
+        if !keep.contains_key(&(content_type, category_id)) {
+            tx.execute("DELETE FROM categories WHERE id = ?1", [id])?;
+            removed += 1;
+        }
+    }
+    Ok(removed)
+}
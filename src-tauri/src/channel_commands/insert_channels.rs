@@ -1,142 +1,655 @@
-use rusqlite::params;
-use serde_json::Value;
-use std::collections::HashMap;
-use tauri::State;
+use rusqlite::{params, Connection, Row, ToSql};
+use std::collections::{HashMap, HashSet};
 
-use crate::{db::DbConnection, models::Error};
+use crate::channel_commands::insert_categories::CategoryMap;
+use crate::{models::Channel, models::Error};
 
+/// How many channels are written between two progress callbacks.
+pub const PROGRESS_INTERVAL: usize = 500;
+
+/// Identifies a channel within its playlist: content type and stream ID.
+/// Series IDs are numbered apart from live and VOD stream IDs, so the
+/// stream ID alone is not unique.
+pub type ChannelKey<'a> = (&'a str, &'a str);
+
+/// The key of a channel, taking a missing content type as `live`.
+pub fn channel_key(channel: &Channel) -> ChannelKey<'_> {
+    (
+        channel.content_type.as_deref().unwrap_or("live"),
+        channel.stream_id.as_str(),
+    )
+}
+
+/// Row counts written by `insert_channels`.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct InsertStats {
+    pub inserted: usize,
+    pub updated: usize,
+    pub unchanged: usize,
+    pub skipped: usize,
+}
+
+/// Channel columns a refresh may change, in the order `ChannelRow` reads
+/// and binds them. `cast` is quoted because it is an SQL keyword.
+pub const CHANNEL_ROW_COLUMNS: [&str; 38] = [
+    "category_id",
+    "category_name",
+    "name",
+    "stream_type",
+    "stream_url",
+    "stream_icon",
+    "epg_channel_id",
+    "num",
+    "tv_archive",
+    "tv_archive_duration",
+    "tvg_name",
+    "tvg_shift",
+    "tvg_language",
+    "tvg_country",
+    "catchup",
+    "catchup_source",
+    "http_user_agent",
+    "http_referrer",
+    "kodi_props",
+    "type_name",
+    "content_type",
+    "added",
+    "series_no",
+    "live",
+    "container_extension",
+    "custom_sid",
+    "direct_source",
+    "plot",
+    "\"cast\"",
+    "director",
+    "genre",
+    "release_date",
+    "rating",
+    "rating_5based",
+    "backdrop_path",
+    "youtube_trailer",
+    "episode_run_time",
+    "cover",
+];
+
+/// The values of `CHANNEL_ROW_COLUMNS` for one channel.
+#[derive(PartialEq)]
+pub struct ChannelRow {
+    pub category_id: Option<String>,
+    pub category_name: Option<String>,
+    pub name: String,
+    pub stream_type: String,
+    pub stream_url: String,
+    pub stream_icon: Option<String>,
+    pub epg_channel_id: Option<String>,
+    pub num: Option<String>,
+    pub tv_archive: Option<i64>,
+    pub tv_archive_duration: Option<i64>,
+    pub tvg_name: Option<String>,
+    pub tvg_shift: Option<f64>,
+    pub tvg_language: Option<String>,
+    pub tvg_country: Option<String>,
+    pub catchup: Option<String>,
+    pub catchup_source: Option<String>,
+    pub http_user_agent: Option<String>,
+    pub http_referrer: Option<String>,
+    /// JSON object of the `#KODIPROP` properties.
+    pub kodi_props: Option<String>,
+    pub type_name: Option<String>,
+    pub content_type: Option<String>,
+    pub added: Option<String>,
+    pub series_no: Option<String>,
+    pub live: Option<String>,
+    pub container_extension: Option<String>,
+    pub custom_sid: Option<String>,
+    pub direct_source: Option<String>,
+    pub plot: Option<String>,
+    pub cast: Option<String>,
+    pub director: Option<String>,
+    pub genre: Option<String>,
+    pub release_date: Option<String>,
+    pub rating: Option<String>,
+    pub rating_5based: Option<f64>,
+    /// JSON array of image URLs.
+    pub backdrop_path: Option<String>,
+    pub youtube_trailer: Option<String>,
+    pub episode_run_time: Option<String>,
+    pub cover: Option<String>,
+}
+
+impl ChannelRow {
+    fn new(channel: &Channel, name: &str, category_name: String) -> Self {
+        ChannelRow {
+            category_id: channel.category_id.clone(),
+            category_name: Some(category_name),
+            name: name.to_string(),
+            stream_type: channel.stream_type.clone(),
+            stream_url: channel.stream_url.clone(),
+            stream_icon: channel.stream_icon.clone(),
+            epg_channel_id: channel.epg_channel_id.clone(),
+            num: channel.num.clone(),
+            tv_archive: channel.tv_archive,
+            tv_archive_duration: channel.tv_archive_duration,
+            tvg_name: channel.tvg_name.clone(),
+            tvg_shift: channel.tvg_shift,
+            tvg_language: channel.tvg_language.clone(),
+            tvg_country: channel.tvg_country.clone(),
+            catchup: channel.catchup.clone(),
+            catchup_source: channel.catchup_source.clone(),
+            http_user_agent: channel.http_user_agent.clone(),
+            http_referrer: channel.http_referrer.clone(),
+            kodi_props: channel
+                .kodi_props
+                .as_ref()
+                .and_then(|props| serde_json::to_string(props).ok()),
+            type_name: channel.type_name.clone(),
+            content_type: Some(channel_key(channel).0.to_string()),
+            added: channel.added.clone(),
+            series_no: channel.series_no.clone(),
+            live: channel.live.clone(),
+            container_extension: channel.container_extension.clone(),
+            custom_sid: channel.custom_sid.clone(),
+            direct_source: channel.direct_source.clone(),
+            plot: channel.plot.clone(),
+            cast: channel.cast.clone(),
+            director: channel.director.clone(),
+            genre: channel.genre.clone(),
+            release_date: channel.release_date.clone(),
+            rating: channel.rating.clone(),
+            rating_5based: channel.rating_5based,
+            backdrop_path: channel
+                .backdrop_path
+                .as_ref()
+                .and_then(|paths| serde_json::to_string(paths).ok()),
+            youtube_trailer: channel.youtube_trailer.clone(),
+            episode_run_time: channel.episode_run_time.clone(),
+            cover: channel.cover.clone(),
+        }
+    }
+
+    /// Reads the columns starting at index `start` of `row`.
+    pub fn read(row: &Row, start: usize) -> rusqlite::Result<Self> {
+        Ok(ChannelRow {
+            category_id: row.get(start)?,
+            category_name: row.get(start + 1)?,
+            name: row.get(start + 2)?,
+            stream_type: row.get(start + 3)?,
+            stream_url: row.get(start + 4)?,
+            stream_icon: row.get(start + 5)?,
+            epg_channel_id: row.get(start + 6)?,
+            num: row.get(start + 7)?,
+            tv_archive: row.get(start + 8)?,
+            tv_archive_duration: row.get(start + 9)?,
+            tvg_name: row.get(start + 10)?,
+            tvg_shift: row.get(start + 11)?,
+            tvg_language: row.get(start + 12)?,
+            tvg_country: row.get(start + 13)?,
+            catchup: row.get(start + 14)?,
+            catchup_source: row.get(start + 15)?,
+            http_user_agent: row.get(start + 16)?,
+            http_referrer: row.get(start + 17)?,
+            kodi_props: row.get(start + 18)?,
+            type_name: row.get(start + 19)?,
+            content_type: row.get(start + 20)?,
+            added: row.get(start + 21)?,
+            series_no: row.get(start + 22)?,
+            live: row.get(start + 23)?,
+            container_extension: row.get(start + 24)?,
+            custom_sid: row.get(start + 25)?,
+            direct_source: row.get(start + 26)?,
+            plot: row.get(start + 27)?,
+            cast: row.get(start + 28)?,
+            director: row.get(start + 29)?,
+            genre: row.get(start + 30)?,
+            release_date: row.get(start + 31)?,
+            rating: row.get(start + 32)?,
+            rating_5based: row.get(start + 33)?,
+            backdrop_path: row.get(start + 34)?,
+            youtube_trailer: row.get(start + 35)?,
+            episode_run_time: row.get(start + 36)?,
+            cover: row.get(start + 37)?,
+        })
+    }
+
+    fn values(&self) -> [&dyn ToSql; 38] {
+        [
+            &self.category_id,
+            &self.category_name,
+            &self.name,
+            &self.stream_type,
+            &self.stream_url,
+            &self.stream_icon,
+            &self.epg_channel_id,
+            &self.num,
+            &self.tv_archive,
+            &self.tv_archive_duration,
+            &self.tvg_name,
+            &self.tvg_shift,
+            &self.tvg_language,
+            &self.tvg_country,
+            &self.catchup,
+            &self.catchup_source,
+            &self.http_user_agent,
+            &self.http_referrer,
+            &self.kodi_props,
+            &self.type_name,
+            &self.content_type,
+            &self.added,
+            &self.series_no,
+            &self.live,
+            &self.container_extension,
+            &self.custom_sid,
+            &self.direct_source,
+            &self.plot,
+            &self.cast,
+            &self.director,
+            &self.genre,
+            &self.release_date,
+            &self.rating,
+            &self.rating_5based,
+            &self.backdrop_path,
+            &self.youtube_trailer,
+            &self.episode_run_time,
+            &self.cover,
+        ]
+    }
+
+    /// Copies the columns onto `channel`.
+    pub fn apply(self, channel: &mut Channel) {
+        channel.category_id = self.category_id;
+        channel.category_name = self.category_name;
+        channel.name = self.name;
+        channel.stream_type = self.stream_type;
+        channel.stream_url = self.stream_url;
+        channel.stream_icon = self.stream_icon;
+        channel.epg_channel_id = self.epg_channel_id;
+        channel.num = self.num;
+        channel.tv_archive = self.tv_archive;
+        channel.tv_archive_duration = self.tv_archive_duration;
+        channel.tvg_name = self.tvg_name;
+        channel.tvg_shift = self.tvg_shift;
+        channel.tvg_language = self.tvg_language;
+        channel.tvg_country = self.tvg_country;
+        channel.catchup = self.catchup;
+        channel.catchup_source = self.catchup_source;
+        channel.http_user_agent = self.http_user_agent;
+        channel.http_referrer = self.http_referrer;
+        channel.kodi_props = self
+            .kodi_props
+            .and_then(|props| serde_json::from_str(&props).ok());
+        channel.type_name = self.type_name;
+        channel.content_type = self.content_type;
+        channel.added = self.added;
+        channel.series_no = self.series_no;
+        channel.live = self.live;
+        channel.container_extension = self.container_extension;
+        channel.custom_sid = self.custom_sid;
+        channel.direct_source = self.direct_source;
+        channel.plot = self.plot;
+        channel.cast = self.cast;
+        channel.director = self.director;
+        channel.genre = self.genre;
+        channel.release_date = self.release_date;
+        channel.rating = self.rating;
+        channel.rating_5based = self.rating_5based;
+        channel.backdrop_path = self
+            .backdrop_path
+            .and_then(|paths| serde_json::from_str(&paths).ok());
+        channel.youtube_trailer = self.youtube_trailer;
+        channel.episode_run_time = self.episode_run_time;
+        channel.cover = self.cover;
+    }
+}
+
+/// Stored rows keyed by content type and stream ID, with their row IDs.
+type StoredChannels = HashMap<(String, String), (i64, ChannelRow)>;
+
+/// The rows stored for the playlist.
+fn load_stored_channels(tx: &Connection, playlist_id: i64) -> Result<StoredChannels, Error> {
+    let mut stmt = tx.prepare(&format!(
+        "SELECT id, stream_id, {} FROM channels WHERE playlist_id = ?1",
+        CHANNEL_ROW_COLUMNS.join(", ")
+    ))?;
+    let rows = stmt
+        .query_map([playlist_id], |row| {
+            let channel = ChannelRow::read(row, 2)?;
+            let content_type = channel.content_type.as_deref().unwrap_or("live");
+            Ok((
+                (content_type.to_string(), row.get::<_, String>(1)?),
+                (row.get(0)?, channel),
+            ))
+        })?
+        .collect::<Result<HashMap<_, _>, _>>()?;
+    Ok(rows)
+}
+
+/// Diffs channels against the rows stored for the playlist, inserting new ones
+/// and updating changed ones in place so their IDs and selection survive.
+/// Uses `tx`; the caller owns the transaction. Returning an error from
+/// `on_progress` aborts the write, e.g. when the import is cancelled.
 pub fn insert_channels(
-    db: State<'_, DbConnection>,
-    all_channels: &Vec<Value>,
-    all_categories: &HashMap<String, (String, String, Option<i64>)>,
-    server_url: &String,
-    username: &String,
-    password: &String,
+    tx: &Connection,
+    all_channels: &[Channel],
+    all_categories: &CategoryMap,
     playlist_id: i64,
-    _stream_type: &String, 
-) -> Result<(), Error> {
-    let mut conn = db.0.lock().unwrap();
-    let tx = conn.transaction()?;
-
-    // Continue using the same transaction for channels
-    for channel in all_channels {
-        // Extract channel data with fallbacks for different JSON structures
-        let stream_id_string: String;
-        let stream_id = if let Some(id) = channel["stream_id"].as_str() {
-            id
-        } else if let Some(id) = channel["stream_id"].as_i64() {
-            stream_id_string = id.to_string();
-            &stream_id_string
-        } else if let Some(id) = channel["num"].as_str() {
-            id
-        } else if let Some(id) = channel["num"].as_i64() {
-            stream_id_string = id.to_string();
-            &stream_id_string
-        } else {
-            "unknown"
-        };
+    on_progress: &mut dyn FnMut(usize) -> Result<(), Error>,
+) -> Result<InsertStats, Error> {
+    let mut stats = InsertStats::default();
+    let mut seen: HashSet<ChannelKey> = HashSet::new();
+    let stored = load_stored_channels(tx, playlist_id)?;
+    let assignments: Vec<String> = CHANNEL_ROW_COLUMNS
+        .iter()
+        .enumerate()
+        .map(|(i, column)| format!("{} = ?{}", column, i + 1))
+        .collect();
+    let mut update = tx.prepare(&format!(
+        "UPDATE channels SET {} WHERE id = ?{}",
+        assignments.join(", "),
+        CHANNEL_ROW_COLUMNS.len() + 1
+    ))?;
+    let placeholders: Vec<String> = (1..=CHANNEL_ROW_COLUMNS.len() + 3)
+        .map(|i| format!("?{}", i))
+        .collect();
+    let mut insert = tx.prepare(&format!(
+        "INSERT INTO channels (playlist_id, stream_id, created_at, {}) VALUES ({})",
+        CHANNEL_ROW_COLUMNS.join(", "),
+        placeholders.join(", ")
+    ))?;
+    for (index, channel) in all_channels.iter().enumerate() {
+        if index > 0 && index % PROGRESS_INTERVAL == 0 {
+            on_progress(index)?;
+        }
+
+        let (content_type, stream_id) = channel_key(channel);
 
-        let name = channel["name"]
-            .as_str()
-            .or(channel["title"].as_str())
-            .unwrap_or("Unknown Channel");
+        // Without a stream ID the row cannot be keyed, and a repeated ID would
+        // overwrite the entry we just wrote.
+        if stream_id.is_empty() || !seen.insert((content_type, stream_id)) {
+            println!(
+                "Skipping channel without unique stream ID: {}",
+                channel.name
+            );
+            stats.skipped += 1;
+            continue;
+        }
 
-        // Get category ID as a String to avoid lifetime issues
-        let category_id_string: Option<String> = if let Some(id) = channel["category_id"].as_str() {
-            Some(id.to_string())
-        } else if let Some(id) = channel["category_id"].as_i64() {
-            Some(id.to_string())
+        if channel.stream_url.is_empty() {
+            println!("Skipping channel without stream URL: {}", channel.name);
+            stats.skipped += 1;
+            continue;
+        }
+
+        let name = if channel.name.is_empty() {
+            "Unknown Channel"
         } else {
-            None
+            channel.name.as_str()
         };
 
-        // Get category ID as &str for database operations
-        let category_id = category_id_string.as_deref();
-
-        println!("Processing channel: {} (ID: {})", name, stream_id);
-
-        let mut _category_id_for_stream: Option<i64> = None;
-        let category_name = match category_id {
-            Some(id) => {
-                println!("Looking for category ID: {}", id);
-                match all_categories.get(id) {
-                    Some((name, _, _)) => {
-                        println!("Found category name: {}", name);
-                        // Get the internal category ID from the categories table
-                        let mut stmt =
-                            tx.prepare("SELECT id FROM categories WHERE category_id = ?1")?;
-                        let mut rows = stmt.query(params![id])?;
-
-                        if let Some(row) = rows.next()? {
-                            let internal_category_id: i64 = row.get(0)?;
-                            _category_id_for_stream = Some(internal_category_id);
-                        } else {
-                            println!("Internal category ID not found for category_id: {}", id);
-                            _category_id_for_stream = None;
-                        }
-                        name.clone()
-                    }
-                    None => {
-                        println!("Category ID not found: {}", id);
-                        _category_id_for_stream = None;
-                        "Uncategorized".to_string()
-                    }
+        let category_id = channel.category_id.as_deref();
+
+        let category_name = category_id
+            .and_then(|id| all_categories.get(&(content_type.to_string(), id.to_string())))
+            .map(|(name, _)| name.clone())
+            .or_else(|| channel.category_name.clone())
+            .unwrap_or_else(|| "Uncategorized".to_string());
+
+        let incoming = ChannelRow::new(channel, name, category_name);
+        match stored.get(&(content_type.to_string(), stream_id.to_string())) {
+            Some((id, existing)) => {
+                if incoming == *existing {
+                    stats.unchanged += 1;
+                    continue;
                 }
+
+                let mut values = incoming.values().to_vec();
+                values.push(id);
+                update.execute(&*values)?;
+                stats.updated += 1;
             }
             None => {
-                println!("Category ID is None");
-                _category_id_for_stream = None;
-                "Uncategorized".to_string()
+                let now = chrono::Utc::now().to_rfc3339();
+                let mut values: Vec<&dyn ToSql> = vec![&playlist_id, &stream_id, &now];
+                values.extend(incoming.values());
+                insert.execute(&*values)?;
+                stats.inserted += 1;
             }
-        };
+        }
+    }
 
-        // Get stream_type from the channel data
-        let stream_type = channel["stream_type"].as_str().unwrap_or("unknown");
+    on_progress(all_channels.len())?;
+    Ok(stats)
+}
 
-        // Insert into streams table
-        let _result = if let Some(cat_id) = _category_id_for_stream {
-            // Insert into streams table with category ID
-            let sql = "INSERT OR IGNORE INTO streams (stream_id, name, stream_type, category_id, added) VALUES (?1, ?2, ?3, ?4, strftime('%s', 'now'))";
-            println!("Executing SQL: {}", sql);
-            println!(
-                "Params: stream_id={}, name={}, stream_type={}, cat_id={}",
-                stream_id, name, stream_type, cat_id
-            );
-            tx.execute(sql, params![stream_id, name, stream_type, cat_id])?
-        } else {
-            // Insert into streams table without category ID
-            let sql = "INSERT OR IGNORE INTO streams (stream_id, name, stream_type, added) VALUES (?1, ?2, ?3, strftime('%s', 'now'))";
-            println!("Executing SQL: {}", sql);
-            println!(
-                "Params: stream_id={}, name={}, stream_type={}",
-                stream_id, name, stream_type
-            );
-            tx.execute(sql, params![stream_id, name, stream_type])?
+/// Deletes the playlist's channels of `content_types` whose key is not in
+/// `keep`, i.e. the ones the provider no longer lists. Returns the number of
+/// rows removed.
+pub fn remove_missing_channels(
+    tx: &Connection,
+    playlist_id: i64,
+    content_types: &[&str],
+    keep: &HashSet<ChannelKey>,
+) -> Result<usize, Error> {
+    let stored = load_stored_channels(tx, playlist_id)?;
+    let mut removed = 0;
+    for ((content_type, stream_id), (id, _)) in &stored {
+        if !content_types.contains(&content_type.as_str()) {
+            continue;
+        }
+        if !keep.contains(&(content_type.as_str(), stream_id.as_str())) {
+            tx.execute("DELETE FROM channels WHERE id = ?1", [id])?;
+            removed += 1;
+        }
+    }
+
+    if removed > 0 {
+        println!(
+            "Removed {} channels no longer provided for playlist {}",
+            removed, playlist_id
+        );
+    }
+    Ok(removed)
+}
+
+/// Tables keyed by the stream IDs of one content type, and their ID column.
+const STREAM_DETAIL_TABLES: [(&str, &str, &str); 5] = [
+    ("live", "epg_channel_map", "stream_id"),
+    ("movie", "vod_metadata", "stream_id"),
+    ("series", "series_info", "series_id"),
+    ("series", "seasons", "series_id"),
+    ("series", "episodes", "series_id"),
+];
+
+/// Moves stored rows to the stream ID now used for their URL, so a change in
+/// how IDs are derived keeps each row, its selection, its favorites, its
+/// guide link and its cached details. Rows whose ID is still in use, or
+/// whose URL is ambiguous, are left alone.
+pub fn rekey_channels(
+    tx: &Connection,
+    playlist_id: i64,
+    channels: &[Channel],
+) -> Result<usize, Error> {
+    let mut by_url: HashMap<&str, Option<ChannelKey>> = HashMap::new();
+    for channel in channels {
+        by_url
+            .entry(channel.stream_url.as_str())
+            .and_modify(|key| *key = None)
+            .or_insert(Some(channel_key(channel)));
+    }
+
+    let stored = load_stored_channels(tx, playlist_id)?;
+    let incoming: HashSet<ChannelKey> = channels.iter().map(channel_key).collect();
+    let mut rekeyed = 0;
+    for ((old_type, old_id), (id, row)) in &stored {
+        if incoming.contains(&(old_type.as_str(), old_id.as_str())) {
+            continue;
+        }
+        let Some(Some((new_type, new_id))) = by_url.get(row.stream_url.as_str()) else {
+            continue;
         };
+        if stored.contains_key(&(new_type.to_string(), new_id.to_string())) {
+            continue;
+        }
 
-        // Also insert into channels table
-        // Get stream_url from the JSON if available, otherwise construct it
-        let stream_url = channel["stream_url"]
-            .as_str()
-            .or(channel["stream"].as_str())
-            .map(|url| url.to_string())
-            .unwrap_or_else(|| {
-                format!(
-                    "{}/live/{}/{}/{}.ts",
-                    server_url, username, password, stream_id
-                )
-            });
-
-        let now = chrono::Utc::now().to_rfc3339();
-        let _result = tx.execute(
-            "INSERT OR REPLACE INTO channels (playlist_id, category_id, category_name, stream_id, name, stream_type, stream_url, created_at)
-         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
-            params![playlist_id, category_id, category_name, stream_id, name, stream_type, stream_url, now],
+        tx.execute(
+            "UPDATE channels SET content_type = ?1, stream_id = ?2 WHERE id = ?3",
+            params![new_type, new_id, id],
+        )?;
+        tx.execute(
+            "UPDATE OR IGNORE favorites SET content_type = ?1, stream_id = ?2
+             WHERE playlist_id = ?3 AND content_type = ?4 AND stream_id = ?5",
+            params![new_type, new_id, playlist_id, old_type, old_id],
         )?;
-        println!("Successfully inserted channel: {}", name);
+        // Details of one content type do not carry over to another
+        for (content_type, table, column) in STREAM_DETAIL_TABLES {
+            if content_type != old_type || content_type != *new_type {
+                continue;
+            }
+            tx.execute(
+                &format!(
+                    "UPDATE OR IGNORE {table} SET {column} = ?1 WHERE playlist_id = ?2 AND {column} = ?3",
+                    table = table,
+                    column = column
+                ),
+                params![new_id, playlist_id, old_id],
+            )?;
+        }
+        rekeyed += 1;
+    }
+
+    if rekeyed > 0 {
+        println!(
+            "Moved {} channels of playlist {} to new stream IDs",
+            rekeyed, playlist_id
+        );
+    }
+    Ok(rekeyed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db;
+
+    fn open_db() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        db::init_db(&conn).unwrap();
+        db::check_and_create_channels_table(&conn).unwrap();
+        for migrate in [
+            db::migrate_db_v1,
+            db::migrate_db_v2,
+            db::migrate_db_v3,
+            db::migrate_db_v4,
+            db::migrate_db_v5,
+            db::migrate_db_v6,
+            db::migrate_db_v7,
+            db::migrate_db_v8,
+            db::migrate_db_v9,
+            db::migrate_db_v10,
+            db::migrate_db_v11,
+            db::migrate_db_v12,
+            db::migrate_db_v13,
+        ] {
+            migrate(&conn).unwrap();
+        }
+        conn.execute(
+            "INSERT INTO playlists (name, server_url, username, password, created_at)
+             VALUES ('Test', 'http://example.com', 'user', 'pass', 'now')",
+            [],
+        )
+        .unwrap();
+        conn
+    }
+
+    fn channel(content_type: &str, stream_id: &str, stream_url: &str) -> Channel {
+        Channel {
+            playlist_id: 1,
+            stream_id: stream_id.to_string(),
+            name: format!("Channel {}", stream_id),
+            stream_type: content_type.to_string(),
+            stream_url: stream_url.to_string(),
+            content_type: Some(content_type.to_string()),
+            ..Default::default()
+        }
+    }
+
+    fn count(conn: &Connection, sql: &str) -> i64 {
+        conn.query_row(sql, [], |row| row.get(0)).unwrap()
     }
 
-    // Commit the transaction to save the channels
-    tx.commit()?;
-    Ok(())
+    #[test]
+    fn rekeys_channels_with_their_details() {
+        let conn = open_db();
+        let old = [
+            channel("live", "1", "http://example.com/live.ts"),
+            channel("movie", "2", "http://example.com/movie/film.mkv"),
+            channel("series", "3", "http://example.com/series/show.mkv"),
+        ];
+        insert_channels(&conn, &old, &CategoryMap::new(), 1, &mut |_| Ok(())).unwrap();
+        conn.execute_batch(
+            "INSERT INTO favorites (playlist_id, stream_id, content_type) VALUES (1, '1', 'live');
+             INSERT INTO epg_channel_map (playlist_id, stream_id, epg_channel_id, manual, updated_at)
+                 VALUES (1, '1', 'bbc1.uk', 1, 'now');
+             INSERT INTO vod_metadata (playlist_id, stream_id, plot) VALUES (1, '2', 'Plot');
+             INSERT INTO series_info (playlist_id, series_id, fetched_at) VALUES (1, '3', 'now');
+             INSERT INTO seasons (playlist_id, series_id, season_number) VALUES (1, '3', 1);",
+        )
+        .unwrap();
+
+        let new = [
+            channel("live", "a", "http://example.com/live.ts"),
+            channel("movie", "b", "http://example.com/movie/film.mkv"),
+            channel("series", "c", "http://example.com/series/show.mkv"),
+        ];
+        assert_eq!(rekey_channels(&conn, 1, &new).unwrap(), 3);
+
+        let stored = load_stored_channels(&conn, 1).unwrap();
+        let mut keys: Vec<(&str, &str)> = stored
+            .keys()
+            .map(|(content_type, stream_id)| (content_type.as_str(), stream_id.as_str()))
+            .collect();
+        keys.sort();
+        assert_eq!(keys, vec![("live", "a"), ("movie", "b"), ("series", "c")]);
+        for (sql, expected) in [
+            ("SELECT COUNT(*) FROM favorites WHERE stream_id = 'a'", 1),
+            (
+                "SELECT COUNT(*) FROM epg_channel_map WHERE stream_id = 'a'",
+                1,
+            ),
+            ("SELECT COUNT(*) FROM vod_metadata WHERE stream_id = 'b'", 1),
+            ("SELECT COUNT(*) FROM series_info WHERE series_id = 'c'", 1),
+            ("SELECT COUNT(*) FROM seasons WHERE series_id = 'c'", 1),
+            (
+                "SELECT COUNT(*) FROM epg_channel_map WHERE stream_id = '1'",
+                0,
+            ),
+        ] {
+            assert_eq!(count(&conn, sql), expected, "{}", sql);
+        }
+
+        // A second refresh finds every ID in use and moves nothing
+        assert_eq!(rekey_channels(&conn, 1, &new).unwrap(), 0);
+    }
+
+    #[test]
+    fn keeps_series_apart_from_streams_with_the_same_id() {
+        let conn = open_db();
+        let channels = [
+            channel("live", "5", "http://example.com/live/5.ts"),
+            channel("series", "5", "http://example.com/series/5.mkv"),
+        ];
+        let stats =
+            insert_channels(&conn, &channels, &CategoryMap::new(), 1, &mut |_| Ok(())).unwrap();
+        assert_eq!((stats.inserted, stats.skipped), (2, 0));
+
+        let stats =
+            insert_channels(&conn, &channels, &CategoryMap::new(), 1, &mut |_| Ok(())).unwrap();
+        assert_eq!(stats.unchanged, 2);
+
+        let keep: HashSet<ChannelKey> = [("live", "5")].into_iter().collect();
+        assert_eq!(
+            remove_missing_channels(&conn, 1, &["live"], &keep).unwrap(),
+            0
+        );
+        assert_eq!(count(&conn, "SELECT COUNT(*) FROM channels"), 2);
+    }
 }
@@ -0,0 +1,596 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use crate::models::Channel;
+
+/// A parsed `#EXTM3U` file.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct M3uPlaylist {
+    /// Attributes of the `#EXTM3U` header line, e.g. `url-tvg`.
+    pub attributes: Vec<(String, String)>,
+    pub entries: Vec<M3uEntry>,
+}
+
+/// One `#EXTINF` entry and the URL that follows it.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct M3uEntry {
+    /// Duration in seconds; `-1` for live streams.
+    pub duration: Option<f64>,
+    /// `key="value"` attributes in the order they appear.
+    pub attributes: Vec<(String, String)>,
+    pub title: String,
+    /// Group from an `#EXTGRP` line, used when there is no `group-title`.
+    pub group: Option<String>,
+    /// `#EXTVLCOPT:key=value` lines, e.g. `http-user-agent`.
+    pub vlc_options: Vec<(String, String)>,
+    /// `#KODIPROP:key=value` lines, e.g. `inputstream.adaptive.license_key`.
+    pub kodi_props: Vec<(String, String)>,
+    pub url: String,
+}
+
+impl M3uEntry {
+    /// Looks up an attribute, ignoring the case of the key. Empty values
+    /// count as missing.
+    pub fn attr(&self, key: &str) -> Option<&str> {
+        find_attr(&self.attributes, key)
+    }
+
+    pub fn tvg_id(&self) -> Option<&str> {
+        self.attr("tvg-id")
+    }
+
+    /// `group-title`, falling back to `#EXTGRP`.
+    pub fn group_title(&self) -> Option<&str> {
+        self.attr("group-title")
+            .or_else(|| self.group.as_deref().filter(|group| !group.is_empty()))
+    }
+
+    /// Display name: the title after the comma, else `tvg-name`.
+    pub fn name(&self) -> Option<&str> {
+        Some(self.title.as_str())
+            .filter(|title| !title.is_empty())
+            .or_else(|| self.attr("tvg-name"))
+    }
+
+    pub fn vlc_option(&self, key: &str) -> Option<&str> {
+        find_attr(&self.vlc_options, key)
+    }
+
+    /// Guesses `live`, `movie` or `series` from the URL, since M3U has no
+    /// field for it: Xtream-style `/movie/` and `/series/` paths, or a
+    /// video file extension for movies.
+    pub fn content_type(&self) -> &'static str {
+        let path = self
+            .url
+            .split(['?', '#'])
+            .next()
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+        if path.contains("/series/") {
+            "series"
+        } else if path.contains("/movie/")
+            || VOD_EXTENSIONS
+                .iter()
+                .any(|extension| path.ends_with(extension))
+        {
+            "movie"
+        } else {
+            "live"
+        }
+    }
+
+    /// Converts the entry into a channel of `playlist_id`. The stream URL is
+    /// taken as is; `stream_id` is left for the caller to assign.
+    pub fn into_channel(self, playlist_id: i64) -> Channel {
+        let attr = |key: &str| self.attr(key).map(str::to_string);
+        let catchup_days = self
+            .attr("catchup-days")
+            .and_then(|days| days.parse::<i64>().ok());
+        let has_catchup =
+            self.attr("catchup").is_some() || matches!(catchup_days, Some(days) if days > 0);
+        let kodi_props: BTreeMap<String, String> = self.kodi_props.iter().cloned().collect();
+        let content_type = self.content_type();
+        let group = self.group_title().unwrap_or("Uncategorized");
+
+        Channel {
+            playlist_id,
+            name: self.name().unwrap_or("Unknown").to_string(),
+            stream_type: content_type.to_string(),
+            category_id: Some(category_id(content_type, group)),
+            category_name: Some(group.to_string()),
+            stream_icon: attr("tvg-logo").or_else(|| attr("logo")),
+            epg_channel_id: self.tvg_id().map(str::to_string),
+            num: attr("tvg-chno").or_else(|| attr("channel-number")),
+            tvg_name: attr("tvg-name"),
+            tvg_shift: self
+                .attr("tvg-shift")
+                .or_else(|| self.attr("timeshift"))
+                .and_then(|shift| shift.parse::<f64>().ok()),
+            tvg_language: attr("tvg-language"),
+            tvg_country: attr("tvg-country"),
+            tv_archive: has_catchup.then_some(1),
+            tv_archive_duration: catchup_days,
+            catchup: attr("catchup").or_else(|| attr("catchup-type")),
+            catchup_source: attr("catchup-source"),
+            http_user_agent: self
+                .vlc_option("http-user-agent")
+                .or_else(|| self.attr("http-user-agent"))
+                .or_else(|| self.attr("user-agent"))
+                .map(str::to_string),
+            http_referrer: self
+                .vlc_option("http-referrer")
+                .or_else(|| self.attr("http-referrer"))
+                .or_else(|| self.attr("referrer"))
+                .map(str::to_string),
+            kodi_props: (!kodi_props.is_empty()).then_some(kodi_props),
+            content_type: Some(content_type.to_string()),
+            stream_url: self.url,
+            ..Default::default()
+        }
+    }
+}
+
+/// File extensions that mark an entry without a `/movie/` path as VOD.
+const VOD_EXTENSIONS: [&str; 6] = [".mp4", ".mkv", ".avi", ".mov", ".wmv", ".m4v"];
+
+/// Synthetic category ID for an M3U group, stable across imports because it
+/// is derived from the content type and group name alone.
+pub fn category_id(content_type: &str, group: &str) -> String {
+    let key = format!("{}/{}", content_type, group);
+    format!("m3u-{:016x}", fnv1a(key.as_bytes()))
+}
+
+fn find_attr<'a>(attributes: &'a [(String, String)], key: &str) -> Option<&'a str> {
+    attributes
+        .iter()
+        .find(|(k, _)| k.eq_ignore_ascii_case(key))
+        .map(|(_, v)| v.as_str())
+        .filter(|v| !v.is_empty())
+}
+
+/// Splits `key="value" key2='value 2' key3=bare` pairs off the front of
+/// `input` until the first comma outside quotes. Returns the attributes and
+/// whatever follows that comma (the title), or `None` if there is no comma.
+fn parse_attributes(input: &str) -> (Vec<(String, String)>, Option<&str>) {
+    let mut attributes = Vec::new();
+    let mut rest = input;
+
+    loop {
+        rest = rest.trim_start();
+        if rest.is_empty() {
+            return (attributes, None);
+        }
+        if let Some(title) = rest.strip_prefix(',') {
+            return (attributes, Some(title));
+        }
+
+        let key_end = rest
+            .find(|c: char| c == '=' || c == ',' || c.is_whitespace())
+            .unwrap_or(rest.len());
+        let key = &rest[..key_end];
+        rest = &rest[key_end..];
+
+        let Some(after_eq) = rest.strip_prefix('=') else {
+            // A bare token without a value carries nothing we can use
+            continue;
+        };
+
+        let (value, remainder) = match after_eq.chars().next() {
+            Some(quote @ ('"' | '\'')) => {
+                let quoted = &after_eq[1..];
+                match quoted.find(quote) {
+                    Some(end) => (&quoted[..end], &quoted[end + 1..]),
+                    // Unterminated quote: the value runs to the end of the line
+                    None => (quoted, ""),
+                }
+            }
+            _ => {
+                let end = after_eq
+                    .find(|c: char| c == ',' || c.is_whitespace())
+                    .unwrap_or(after_eq.len());
+                (&after_eq[..end], &after_eq[end..])
+            }
+        };
+
+        if !key.is_empty() {
+            attributes.push((key.to_string(), value.trim().to_string()));
+        }
+        rest = remainder;
+    }
+}
+
+/// Parses the part of an `#EXTINF:` line after the colon into its
+/// duration, attributes and title.
+pub fn parse_extinf(info: &str) -> (Option<f64>, Vec<(String, String)>, String) {
+    let info = info.trim();
+    let duration_end = info
+        .find(|c: char| c == ',' || c.is_whitespace())
+        .unwrap_or(info.len());
+    let duration = info[..duration_end].parse::<f64>().ok();
+
+    let (attributes, title) = parse_attributes(&info[duration_end..]);
+    (
+        duration,
+        attributes,
+        title
+            .map(|title| title.trim().to_string())
+            .unwrap_or_default(),
+    )
+}
+
+/// 64-bit FNV-1a, used instead of `DefaultHasher` because stream IDs must not
+/// change between Rust releases.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// Derives an ID for an entry that does not depend on its position:
+/// the numeric stream ID of Xtream-style URLs (`.../live/u/p/1234.ts`),
+/// otherwise a hash of the URL without its query, else of the `tvg-id`.
+fn base_stream_id(entry: &M3uEntry) -> String {
+    let without_query = entry.url.split(['?', '#']).next().unwrap_or_default();
+    let last_segment = without_query.rsplit('/').next().unwrap_or_default();
+    let stem = last_segment.split('.').next().unwrap_or_default();
+    if !stem.is_empty() && stem.bytes().all(|b| b.is_ascii_digit()) {
+        return stem.to_string();
+    }
+
+    let path = without_query
+        .split_once("://")
+        .map_or(without_query, |(_, rest)| rest);
+    if !path.trim_matches('/').is_empty() {
+        return format!("url-{:016x}", fnv1a(path.as_bytes()));
+    }
+
+    match entry.tvg_id() {
+        Some(tvg_id) => format!("tvg-{}", tvg_id),
+        None => format!("name-{:016x}", fnv1a(entry.title.as_bytes())),
+    }
+}
+
+/// Assigns every entry a stream ID that survives reordering of the file.
+/// Entries sharing a base ID are all told apart by a hash of their URL and
+/// name, and failing that by a counter in file order.
+pub fn stable_stream_ids(entries: &[M3uEntry]) -> Vec<String> {
+    let bases: Vec<String> = entries.iter().map(base_stream_id).collect();
+    let mut counts: HashMap<&str, usize> = HashMap::new();
+    for base in &bases {
+        *counts.entry(base.as_str()).or_default() += 1;
+    }
+
+    let mut used: HashSet<String> = HashSet::new();
+    entries
+        .iter()
+        .zip(&bases)
+        .map(|(entry, base)| {
+            let mut id = base.clone();
+            if counts[base.as_str()] > 1 {
+                let without_query = entry.url.split(['?', '#']).next().unwrap_or_default();
+                let key = format!("{}\n{}", without_query, entry.name().unwrap_or_default());
+                id = format!("{}-{:08x}", base, fnv1a(key.as_bytes()) as u32);
+            }
+
+            let mut counter = 2;
+            let mut candidate = id.clone();
+            while !used.insert(candidate.clone()) {
+                candidate = format!("{}-{}", id, counter);
+                counter += 1;
+            }
+            candidate
+        })
+        .collect()
+}
+
+/// Splits a `#EXTVLCOPT:` or `#KODIPROP:` directive at its first `=`.
+fn parse_option(option: &str) -> Option<(String, String)> {
+    let (key, value) = option.split_once('=')?;
+    Some((key.trim().to_string(), value.trim().to_string()))
+}
+
+/// Parses M3U content. Returns `None` if it does not start with `#EXTM3U`.
+/// URLs without a preceding `#EXTINF` line are skipped, as are `#EXTINF`
+/// lines that are never followed by a URL. `#EXTGRP`, `#EXTVLCOPT` and
+/// `#KODIPROP` lines apply to the next URL, whether they come before or
+/// after its `#EXTINF`.
+pub fn parse_m3u(content: &str) -> Option<M3uPlaylist> {
+    let mut lines = content
+        .trim_start_matches('\u{feff}')
+        .lines()
+        .map(str::trim)
+        .skip_while(|line| line.is_empty());
+
+    let header = lines.next()?.strip_prefix("#EXTM3U")?;
+    let mut playlist = M3uPlaylist {
+        attributes: parse_attributes(header).0,
+        entries: Vec::new(),
+    };
+
+    let mut current = M3uEntry::default();
+    let mut has_extinf = false;
+    for line in lines {
+        if line.is_empty() {
+            continue;
+        }
+
+        if let Some(info) = line.strip_prefix("#EXTINF:") {
+            let (duration, attributes, title) = parse_extinf(info);
+            current.duration = duration;
+            current.attributes = attributes;
+            current.title = title;
+            has_extinf = true;
+        } else if let Some(group) = line.strip_prefix("#EXTGRP:") {
+            current.group = Some(group.trim().to_string());
+        } else if let Some(option) = line.strip_prefix("#EXTVLCOPT:") {
+            current.vlc_options.extend(parse_option(option));
+        } else if let Some(prop) = line.strip_prefix("#KODIPROP:") {
+            current.kodi_props.extend(parse_option(prop));
+        } else if !line.starts_with('#') {
+            let mut entry = std::mem::take(&mut current);
+            if std::mem::take(&mut has_extinf) {
+                entry.url = line.to_string();
+                playlist.entries.push(entry);
+            }
+        }
+    }
+
+    Some(playlist)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_quoted_attributes_with_spaces() {
+        let (duration, attributes, title) = parse_extinf(
+            r#"-1 tvg-id="bbc1.uk" tvg-name="BBC One HD" group-title="Sports HD",BBC One"#,
+        );
+        assert_eq!(duration, Some(-1.0));
+        assert_eq!(
+            attributes,
+            vec![
+                ("tvg-id".to_string(), "bbc1.uk".to_string()),
+                ("tvg-name".to_string(), "BBC One HD".to_string()),
+                ("group-title".to_string(), "Sports HD".to_string()),
+            ]
+        );
+        assert_eq!(title, "BBC One");
+    }
+
+    #[test]
+    fn keeps_commas_inside_quotes_and_title() {
+        let (_, attributes, title) = parse_extinf(
+            r#"-1 tvg-logo="http://logo.example/a,b.png" group-title="News, Politics",CNN, International"#,
+        );
+        assert_eq!(attributes[0].1, "http://logo.example/a,b.png");
+        assert_eq!(attributes[1].1, "News, Politics");
+        assert_eq!(title, "CNN, International");
+    }
+
+    #[test]
+    fn handles_duration_variants_and_missing_attributes() {
+        assert_eq!(parse_extinf("0,Radio").0, Some(0.0));
+        assert_eq!(
+            parse_extinf("5400.5 ,Movie"),
+            (Some(5400.5), vec![], "Movie".to_string())
+        );
+        let (duration, attributes, title) = parse_extinf("-1,");
+        assert_eq!(duration, Some(-1.0));
+        assert!(attributes.is_empty());
+        assert_eq!(title, "");
+    }
+
+    #[test]
+    fn accepts_single_quotes_and_bare_values() {
+        let (_, attributes, title) =
+            parse_extinf("-1 tvg-id=abc.de tvg-name='Das Erste' catchup-days=7,Das Erste HD");
+        assert_eq!(
+            attributes,
+            vec![
+                ("tvg-id".to_string(), "abc.de".to_string()),
+                ("tvg-name".to_string(), "Das Erste".to_string()),
+                ("catchup-days".to_string(), "7".to_string()),
+            ]
+        );
+        assert_eq!(title, "Das Erste HD");
+    }
+
+    #[test]
+    fn tolerates_unterminated_quotes_and_missing_title() {
+        let (_, attributes, title) = parse_extinf(r#"-1 tvg-id="broken"#);
+        assert_eq!(
+            attributes,
+            vec![("tvg-id".to_string(), "broken".to_string())]
+        );
+        assert_eq!(title, "");
+    }
+
+    #[test]
+    fn parses_provider_file() {
+        let content = "\u{feff}#EXTM3U url-tvg=\"http://epg.example/xmltv.php\" x-tvg-url=\"http://epg2.example\"\r\n\
+            #EXTINF:-1 tvg-id=\"sky.uk\" tvg-name=\"UK: Sky Sports\" tvg-logo=\"http://l/1.png\" group-title=\"UK | Sports\",UK: Sky Sports\r\n\
+            http://host:8080/live/u/p/1.ts\r\n\
+            \r\n\
+            #EXTINF:-1,Fallback Group\r\n\
+            #EXTGRP:Music\r\n\
+            #EXTVLCOPT:http-user-agent=Mozilla\r\n\
+            http://host:8080/live/u/p/2.ts\r\n\
+            #EXTINF:-1 tvg-name=\"Orphan\",\r\n\
+            #EXTINF:-1 tvg-name=\"Named By Attribute\",\r\n\
+            http://host:8080/movie/u/p/3.mkv\r\n\
+            http://host:8080/no-extinf.ts\r\n";
+
+        let playlist = parse_m3u(content).expect("valid playlist");
+        assert_eq!(
+            find_attr(&playlist.attributes, "URL-TVG"),
+            Some("http://epg.example/xmltv.php")
+        );
+        assert_eq!(playlist.entries.len(), 3);
+
+        let sky = &playlist.entries[0];
+        assert_eq!(sky.tvg_id(), Some("sky.uk"));
+        assert_eq!(sky.group_title(), Some("UK | Sports"));
+        assert_eq!(sky.name(), Some("UK: Sky Sports"));
+        assert_eq!(sky.attr("tvg-logo"), Some("http://l/1.png"));
+        assert_eq!(sky.url, "http://host:8080/live/u/p/1.ts");
+
+        let music = &playlist.entries[1];
+        assert_eq!(music.group_title(), Some("Music"));
+        assert_eq!(music.tvg_id(), None);
+
+        let named = &playlist.entries[2];
+        assert_eq!(named.name(), Some("Named By Attribute"));
+        assert_eq!(named.url, "http://host:8080/movie/u/p/3.mkv");
+    }
+
+    #[test]
+    fn maps_extended_attributes_onto_channel() {
+        let content = "#EXTM3U\n\
+            #KODIPROP:inputstream.adaptive.manifest_type=mpd\n\
+            #EXTINF:-1 tvg-id=\"zdf.de\" tvg-name=\"ZDF HD\" tvg-logo=\"http://l/zdf.png\" tvg-chno=\"2\" tvg-shift=\"-1.5\" tvg-language=\"German\" tvg-country=\"DE\" catchup=\"append\" catchup-days=\"7\" catchup-source=\"?utc={utc}\",ZDF\n\
+            #EXTGRP:Germany\n\
+            #EXTVLCOPT:http-user-agent=Mozilla/5.0 (X11; Linux)\n\
+            #EXTVLCOPT:http-referrer=http://portal.example/\n\
+            #KODIPROP:inputstream.adaptive.license_type=clearkey\n\
+            http://host/zdf.mpd\n\
+            #EXTINF:-1,Plain\n\
+            http://host/plain.ts\n";
+
+        let mut entries = parse_m3u(content).expect("valid playlist").entries;
+        assert_eq!(entries.len(), 2);
+
+        let plain = entries.pop().unwrap();
+        assert!(plain.kodi_props.is_empty() && plain.vlc_options.is_empty());
+        let plain = plain.into_channel(1);
+        assert_eq!(plain.category_name.as_deref(), Some("Uncategorized"));
+        assert_eq!(plain.tv_archive, None);
+        assert_eq!(plain.kodi_props, None);
+
+        let zdf = entries.pop().unwrap().into_channel(1);
+        assert_eq!(zdf.name, "ZDF");
+        assert_eq!(zdf.category_name.as_deref(), Some("Germany"));
+        assert_eq!(zdf.epg_channel_id.as_deref(), Some("zdf.de"));
+        assert_eq!(zdf.stream_icon.as_deref(), Some("http://l/zdf.png"));
+        assert_eq!(zdf.num.as_deref(), Some("2"));
+        assert_eq!(zdf.tvg_name.as_deref(), Some("ZDF HD"));
+        assert_eq!(zdf.tvg_shift, Some(-1.5));
+        assert_eq!(zdf.tvg_language.as_deref(), Some("German"));
+        assert_eq!(zdf.tvg_country.as_deref(), Some("DE"));
+        assert_eq!(zdf.tv_archive, Some(1));
+        assert_eq!(zdf.tv_archive_duration, Some(7));
+        assert_eq!(zdf.catchup.as_deref(), Some("append"));
+        assert_eq!(zdf.catchup_source.as_deref(), Some("?utc={utc}"));
+        assert_eq!(
+            zdf.http_user_agent.as_deref(),
+            Some("Mozilla/5.0 (X11; Linux)")
+        );
+        assert_eq!(zdf.http_referrer.as_deref(), Some("http://portal.example/"));
+        let props = zdf.kodi_props.expect("kodi props");
+        assert_eq!(props.len(), 2);
+        assert_eq!(
+            props
+                .get("inputstream.adaptive.license_type")
+                .map(String::as_str),
+            Some("clearkey")
+        );
+        assert_eq!(zdf.stream_url, "http://host/zdf.mpd");
+    }
+
+    fn entry(url: &str, extinf: &str) -> M3uEntry {
+        let (duration, attributes, title) = parse_extinf(extinf);
+        M3uEntry {
+            duration,
+            attributes,
+            title,
+            url: url.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn stream_ids_do_not_depend_on_order() {
+        let a = entry("http://host/live/u/p/1234.ts", "-1,A");
+        let b = entry("http://cdn.example/hls/news/index.m3u8?token=1", "-1,B");
+        let c = entry("http://cdn.example/hls/sport/index.m3u8", "-1,C");
+
+        let ids = stable_stream_ids(&[a.clone(), b.clone(), c.clone()]);
+        assert_eq!(ids[0], "1234");
+        assert!(ids[1].starts_with("url-"));
+        assert_ne!(ids[1], ids[2]);
+
+        let reordered = stable_stream_ids(&[c, a, b]);
+        assert_eq!(
+            reordered,
+            vec![ids[2].clone(), ids[0].clone(), ids[1].clone()]
+        );
+    }
+
+    #[test]
+    fn stream_ids_ignore_query_tokens() {
+        let first = stable_stream_ids(&[entry("http://cdn/x/index.m3u8?token=1", "-1,X")]);
+        let second = stable_stream_ids(&[entry("http://cdn/x/index.m3u8?token=2", "-1,X")]);
+        assert_eq!(first, second);
+    }
+
+    #[test]
+    fn colliding_stream_ids_are_disambiguated() {
+        let seven = entry("http://host/live/u/p/7.ts", "-1,Seven");
+        let backup = entry("http://backup/live/u/p/7.ts", "-1,Seven");
+        let duplicate = entry("http://host/live/u/p/7.ts", "-1,Seven");
+
+        let ids = stable_stream_ids(&[seven.clone(), backup.clone(), duplicate]);
+        assert!(ids[0].starts_with("7-"));
+        assert!(ids[1].starts_with("7-"));
+        assert_ne!(ids[0], ids[1]);
+        assert_eq!(ids[2], format!("{}-2", ids[0]));
+
+        let swapped = stable_stream_ids(&[backup, seven]);
+        assert_eq!(swapped, vec![ids[1].clone(), ids[0].clone()]);
+    }
+
+    #[test]
+    fn stream_ids_fall_back_to_tvg_id() {
+        let ids = stable_stream_ids(&[entry("rtp://", r#"-1 tvg-id="one.uk",One"#)]);
+        assert_eq!(ids, vec!["tvg-one.uk".to_string()]);
+    }
+
+    #[test]
+    fn rejects_content_without_header() {
+        assert_eq!(parse_m3u(""), None);
+        assert_eq!(parse_m3u("#EXTINF:-1,Test\nhttp://a/b.ts"), None);
+    }
+
+    #[test]
+    fn guesses_content_type_from_url() {
+        let entry = |url: &str| M3uEntry {
+            url: url.to_string(),
+            ..Default::default()
+        };
+        assert_eq!(entry("http://h/live/u/p/1.ts").content_type(), "live");
+        assert_eq!(entry("http://h/movie/u/p/2.mkv").content_type(), "movie");
+        assert_eq!(
+            entry("http://h/films/Heat.MP4?token=x").content_type(),
+            "movie"
+        );
+        assert_eq!(entry("http://h/series/u/p/3.mp4").content_type(), "series");
+        assert_eq!(entry("http://h/stream.m3u8").content_type(), "live");
+    }
+
+    #[test]
+    fn category_ids_are_stable_per_type_and_group() {
+        let m3u = parse_m3u(
+            "#EXTM3U\n#EXTINF:-1 group-title=\"News\",A\nhttp://h/live/u/p/1.ts\n#EXTINF:-1 group-title=\"News\",B\nhttp://h/movie/u/p/2.mp4\n",
+        )
+        .unwrap();
+        let channels: Vec<Channel> = m3u
+            .entries
+            .into_iter()
+            .map(|entry| entry.into_channel(1))
+            .collect();
+        assert_eq!(channels[0].category_id, Some(category_id("live", "News")));
+        assert_eq!(channels[1].category_id, Some(category_id("movie", "News")));
+        assert_ne!(channels[0].category_id, channels[1].category_id);
+        assert_eq!(channels[1].content_type.as_deref(), Some("movie"));
+    }
+}
@@ -1,18 +1,54 @@
+use rusqlite::Row;
 use tauri::State;
 
 pub mod category_commands;
+pub mod epg_commands;
+pub mod epg_mapping;
+pub mod epg_queries;
 pub mod import_commands;
+pub mod series_commands;
+pub mod short_epg;
+pub mod vod_commands;
 
+pub mod auto_refresh;
+mod epg_matcher;
+pub mod export_m3u;
 pub mod fetch_api;
-mod extract_categories;
-mod extract_channels;
 mod fetch_data;
+pub mod import_registry;
 mod insert_categories;
 mod insert_channels;
+mod m3u_parser;
 mod process_m3u;
+mod xmltv_parser;
+pub mod xtream_client;
+pub mod xtream_models;
 
+use crate::channel_commands::insert_channels::{ChannelRow, CHANNEL_ROW_COLUMNS};
 use crate::{db::DbConnection, models::Channel, models::Error};
 
+/// Columns read by `channel_from_row`, in order.
+fn channel_columns() -> String {
+    format!(
+        "id, playlist_id, stream_id, authenticated_stream_url, created_at, is_selected, {}",
+        CHANNEL_ROW_COLUMNS.join(", ")
+    )
+}
+
+fn channel_from_row(row: &Row) -> rusqlite::Result<Channel> {
+    let mut channel = Channel {
+        id: row.get(0)?,
+        playlist_id: row.get(1)?,
+        stream_id: row.get(2)?,
+        authenticated_stream_url: row.get(3)?,
+        created_at: row.get(4)?,
+        is_selected: row.get(5)?,
+        ..Default::default()
+    };
+    ChannelRow::read(row, 6)?.apply(&mut channel);
+    Ok(channel)
+}
+
 #[tauri::command(rename_all = "camelCase")]
 pub async fn fetch_channels(
     db: State<'_, DbConnection>,
@@ -20,48 +56,12 @@ pub async fn fetch_channels(
 ) -> Result<Vec<Channel>, Error> {
     println!("fetch_channels called with playlist_id: {}", playlist_id);
     let conn = db.0.lock().unwrap();
-    let mut stmt = conn.prepare("SELECT * FROM channels WHERE playlist_id = ?")?;
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM channels WHERE playlist_id = ?",
+        channel_columns()
+    ))?;
     let channels = stmt
-        .query_map([playlist_id], |row| {
-            Ok(Channel {
-                id: row.get(0)?,
-                playlist_id: row.get(1)?,
-                category_id: row.get(2)?,
-                category_name: row.get(3)?,
-                stream_id: row.get(4)?,
-                name: row.get(5)?,
-                stream_type: row.get(6)?,
-                stream_url: row.get(7)?,
-                authenticated_stream_url: row.get(8)?,
-                created_at: row.get(9)?,
-                is_selected: row.get(10)?,
-                // Set default values for other fields that aren't in the database
-                type_name: None,
-                stream_icon: None,
-                epg_channel_id: None,
-                added: None,
-                series_no: None,
-                live: None,
-                container_extension: None,
-                custom_sid: None,
-                tv_archive: None,
-                direct_source: None,
-                tv_archive_duration: None,
-                num: None,
-                plot: None,
-                cast: None,
-                director: None,
-                genre: None,
-                release_date: None,
-                rating: None,
-                rating_5based: None,
-                backdrop_path: None,
-                youtube_trailer: None,
-                episode_run_time: None,
-                cover: None,
-                content_type: None,
-            })
-        })?
+        .query_map([playlist_id], channel_from_row)?
         .collect::<Result<Vec<_>, _>>()?;
     Ok(channels)
 }
@@ -69,50 +69,11 @@ pub async fn fetch_channels(
 #[tauri::command(rename_all = "camelCase")]
 pub async fn get_selected_channel(db: State<'_, DbConnection>) -> Result<Option<Channel>, Error> {
     let conn = db.0.lock().unwrap();
-    let mut stmt = conn.prepare("SELECT * FROM channels WHERE is_selected = 1 LIMIT 1")?;
-    let channel = stmt
-        .query_map([], |row| {
-            Ok(Channel {
-                id: row.get(0)?,
-                playlist_id: row.get(1)?,
-                category_id: row.get(2)?,
-                category_name: row.get(3)?,
-                stream_id: row.get(4)?,
-                name: row.get(5)?,
-                stream_type: row.get(6)?,
-                stream_url: row.get(7)?,
-                authenticated_stream_url: row.get(8)?,
-                created_at: row.get(9)?,
-                is_selected: row.get(10)?,
-                // Set default values for other fields that aren't in the database
-                type_name: None,
-                stream_icon: None,
-                epg_channel_id: None,
-                added: None,
-                series_no: None,
-                live: None,
-                container_extension: None,
-                custom_sid: None,
-                tv_archive: None,
-                direct_source: None,
-                tv_archive_duration: None,
-                num: None,
-                plot: None,
-                cast: None,
-                director: None,
-                genre: None,
-                release_date: None,
-                rating: None,
-                rating_5based: None,
-                backdrop_path: None,
-                youtube_trailer: None,
-                episode_run_time: None,
-                cover: None,
-                content_type: None,
-            })
-        })?
-        .next()
-        .transpose()?;
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM channels WHERE is_selected = 1 LIMIT 1",
+        channel_columns()
+    ))?;
+    let channel = stmt.query_map([], channel_from_row)?.next().transpose()?;
     Ok(channel)
 }
 
@@ -1,98 +1,134 @@
-use chrono;
-use rusqlite::params;
+use std::collections::HashSet;
 use tauri::State;
 
+use crate::channel_commands::insert_categories::{
+    insert_categories, remove_missing_categories, CategoryMap,
+};
+use crate::channel_commands::insert_channels::{
+    channel_key, insert_channels, rekey_channels, remove_missing_channels, ChannelKey,
+};
+use crate::channel_commands::m3u_parser::{parse_m3u, stable_stream_ids};
+use crate::models::{Channel, EndpointResult, ImportReport};
 use crate::{db::DbConnection, models::Error};
 
-#[tauri::command(rename_all = "camelCase")]
-pub async fn process_m3u_content(
-    db: State<'_, DbConnection>,
+/// Content types an M3U file can hold.
+const CONTENT_TYPES: [&str; 3] = ["live", "movie", "series"];
+
+/// Parses M3U content and syncs the playlist's channels with it in one
+/// transaction. `endpoint` and `status` describe where the content came from
+/// in the report. `on_progress` gets the channels written so far and the
+/// number parsed; returning an error from it aborts the write.
+pub fn import_m3u_content(
+    db: &DbConnection,
     playlist_id: i64,
+    endpoint: &str,
+    status: Option<u16>,
     m3u_content: &str,
-    _server_url: &str,
-    _username: &str,
-    _password: &str,
-) -> Result<bool, Error> {
+    on_progress: &mut dyn FnMut(usize, usize) -> Result<(), Error>,
+) -> Result<ImportReport, Error> {
     println!("Processing M3U content...");
 
-    // Parse the M3U content
-    let lines: Vec<&str> = m3u_content.lines().collect();
-    if lines.is_empty() || !lines[0].starts_with("#EXTM3U") {
-        println!("Invalid M3U format");
-        return Ok(false);
-    }
-
-    let mut channels = Vec::new();
-    let mut current_info = String::new();
-
-    for i in 1..lines.len() {
-        let line = lines[i].trim();
-
-        if line.starts_with("#EXTINF:") {
-            current_info = line.to_string();
-        } else if !line.is_empty() && !line.starts_with("#") && !current_info.is_empty() {
-            // This is a URL line following an EXTINF line
-            let stream_url = line.to_string();
-
-            // Parse the EXTINF line to extract channel name and other metadata
-            let mut channel_name = "Unknown";
-            let mut category_name = "Uncategorized";
-            let mut _tvg_id = "";
-
-            // Extract channel name from the EXTINF line
-            if let Some(name_start) = current_info.rfind(',') {
-                channel_name = &current_info[name_start + 1..].trim();
-            }
+    let mut report = ImportReport {
+        playlist_id,
+        ..Default::default()
+    };
 
-            // Extract other metadata from the EXTINF line
-            let info_parts: Vec<&str> = current_info.split(' ').collect();
-            for part in info_parts {
-                if part.starts_with("tvg-id=\"") && part.ends_with("\"") {
-                    _tvg_id = &part[8..part.len() - 1];
-                } else if part.starts_with("group-title=\"") && part.ends_with("\"") {
-                    category_name = &part[13..part.len() - 1];
-                }
-            }
-
-            // Generate a unique stream ID
-            let stream_id = format!("{}", channels.len() + 1);
-
-            channels.push((
-                stream_id,
-                channel_name.to_string(),
-                category_name.to_string(),
-                stream_url,
-            ));
-            current_info = String::new();
+    let Some(m3u) = parse_m3u(m3u_content) else {
+        println!("Invalid M3U format");
+        report.endpoints.push(EndpointResult {
+            endpoint: endpoint.to_string(),
+            success: false,
+            status,
+            item_count: 0,
+            error: Some("Invalid M3U format".to_string()),
+        });
+        report.finish();
+        return Ok(report);
+    };
+
+    let stream_ids = stable_stream_ids(&m3u.entries);
+    let channels: Vec<Channel> = m3u
+        .entries
+        .into_iter()
+        .zip(stream_ids)
+        .map(|(entry, stream_id)| Channel {
+            stream_id,
+            ..entry.into_channel(playlist_id)
+        })
+        .collect();
+
+    // Groups become categories of the content type their entries were
+    // guessed to be, so the same group can exist for live and movies
+    let mut categories = CategoryMap::new();
+    for channel in &channels {
+        if let (Some(category_id), Some(name), Some(content_type)) = (
+            &channel.category_id,
+            &channel.category_name,
+            &channel.content_type,
+        ) {
+            categories.insert(
+                (content_type.clone(), category_id.clone()),
+                (name.clone(), None),
+            );
         }
     }
 
     println!("Found {} channels in M3U content", channels.len());
-
-    // Insert channels into the database
+    for channel in &channels {
+        match channel.content_type.as_deref() {
+            Some("movie") => report.vod += 1,
+            Some("series") => report.series += 1,
+            _ => report.live += 1,
+        }
+    }
+    report.categories = categories.len();
+    report.endpoints.push(EndpointResult {
+        endpoint: endpoint.to_string(),
+        success: true,
+        status,
+        item_count: channels.len(),
+        error: None,
+    });
+
+    // Sync the playlist with the file in one transaction: channels missing
+    // from the new content are removed, surviving ones keep their rows.
+    let mut conn = db.0.lock().unwrap();
+    let tx = conn.transaction()?;
+
+    // Earlier imports numbered channels by position; carry those rows over
+    rekey_channels(&tx, playlist_id, &channels)?;
+    insert_categories(&tx, playlist_id, &categories)?;
+    let total = channels.len();
+    let stats = insert_channels(&tx, &channels, &categories, playlist_id, &mut |current| {
+        on_progress(current, total)
+    })?;
+    // An empty file is more likely a provider glitch than an empty lineup
     if !channels.is_empty() {
-        let mut conn = db.0.lock().unwrap();
-        let tx = conn.transaction()?;
-
-        for (stream_id, name, category_name, stream_url) in channels {
-            let now = chrono::Utc::now().to_rfc3339();
-            let result = tx.execute(
-                "INSERT INTO channels (playlist_id, category_id, category_name, stream_id, name, stream_type, stream_url, created_at) 
-                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
-                params![playlist_id, None::<String>, category_name, stream_id, name, "live", stream_url, now],
-            );
+        remove_missing_categories(&tx, playlist_id, &CONTENT_TYPES, &categories)?;
+        let keep: HashSet<ChannelKey> = channels.iter().map(channel_key).collect();
+        report.removed = remove_missing_channels(&tx, playlist_id, &CONTENT_TYPES, &keep)?;
+    }
+    tx.commit()?;
 
-            match result {
-                Ok(_) => {}
-                Err(e) => {
-                    println!("Error inserting channel from M3U: {}", e);
-                }
-            }
-        }
+    report.inserted = stats.inserted;
+    report.updated = stats.updated;
+    report.unchanged = stats.unchanged;
+    report.skipped = stats.skipped;
+    report.finish();
 
-        tx.commit()?;
-        return Ok(true);
-    }
+    Ok(report)
+}
 
-    Ok(false)
+#[tauri::command(rename_all = "camelCase")]
+pub async fn process_m3u_content(
+    db: State<'_, DbConnection>,
+    playlist_id: i64,
+    m3u_content: &str,
+    _server_url: &str,
+    _username: &str,
+    _password: &str,
+) -> Result<ImportReport, Error> {
+    import_m3u_content(&db, playlist_id, "m3u", None, m3u_content, &mut |_, _| {
+        Ok(())
+    })
 }
@@ -0,0 +1,229 @@
+use chrono::{DateTime, Duration, Utc};
+use rusqlite::{params, Connection, OptionalExtension};
+use tauri::State;
+
+use crate::channel_commands::xtream_client::XtreamClient;
+use crate::channel_commands::xtream_models::SeriesInfo;
+use crate::db::DbConnection;
+use crate::models::{Episode, Error, Season, SeriesDetails, SourceType, ValidationError};
+use crate::playlist_commands::load_playlist;
+
+/// How long fetched seasons and episodes are served before asking again.
+const SERIES_INFO_TTL_HOURS: i64 = 24;
+
+const EPISODE_COLUMNS: &str = "episode_id, season_number, episode_num, title, duration_secs, duration, plot, cover, rating, release_date, container_extension, stream_url, added";
+
+/// Reads the cached details of a series, if it was ever fetched.
+fn load_series_details(
+    conn: &Connection,
+    playlist_id: i64,
+    series_id: &str,
+) -> Result<Option<SeriesDetails>, Error> {
+    let fetched_at: Option<String> = conn
+        .query_row(
+            "SELECT fetched_at FROM series_info WHERE playlist_id = ?1 AND series_id = ?2",
+            params![playlist_id, series_id],
+            |row| row.get(0),
+        )
+        .optional()?;
+    let Some(fetched_at) = fetched_at else {
+        return Ok(None);
+    };
+
+    let mut stmt = conn.prepare(
+        "SELECT season_number, name, overview, air_date, episode_count, cover FROM seasons
+         WHERE playlist_id = ?1 AND series_id = ?2 ORDER BY season_number",
+    )?;
+    let seasons = stmt
+        .query_map(params![playlist_id, series_id], |row| {
+            Ok(Season {
+                season_number: row.get(0)?,
+                name: row.get(1)?,
+                overview: row.get(2)?,
+                air_date: row.get(3)?,
+                episode_count: row.get(4)?,
+                cover: row.get(5)?,
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM episodes WHERE playlist_id = ?1 AND series_id = ?2
+         ORDER BY season_number, episode_num, id",
+        EPISODE_COLUMNS
+    ))?;
+    let episodes = stmt
+        .query_map(params![playlist_id, series_id], |row| {
+            Ok(Episode {
+                episode_id: row.get(0)?,
+                season_number: row.get(1)?,
+                episode_num: row.get(2)?,
+                title: row.get(3)?,
+                duration_secs: row.get(4)?,
+                duration: row.get(5)?,
+                plot: row.get(6)?,
+                cover: row.get(7)?,
+                rating: row.get(8)?,
+                release_date: row.get(9)?,
+                container_extension: row.get(10)?,
+                stream_url: row.get(11)?,
+                added: row.get(12)?,
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(Some(SeriesDetails {
+        playlist_id,
+        series_id: series_id.to_string(),
+        seasons,
+        episodes,
+        fetched_at,
+        stale: false,
+    }))
+}
+
+/// Replaces the cached seasons and episodes of a series in one transaction.
+fn save_series_info(
+    conn: &mut Connection,
+    client: &XtreamClient,
+    playlist_id: i64,
+    series_id: &str,
+    info: SeriesInfo,
+    fetched_at: &str,
+) -> Result<(), Error> {
+    let tx = conn.transaction()?;
+    for table in ["seasons", "episodes"] {
+        tx.execute(
+            &format!(
+                "DELETE FROM {} WHERE playlist_id = ?1 AND series_id = ?2",
+                table
+            ),
+            params![playlist_id, series_id],
+        )?;
+    }
+
+    for season in info.seasons {
+        // Seasons without a number cannot be matched to their episodes
+        let Some(season_number) = season.season_number else {
+            continue;
+        };
+        tx.execute(
+            "INSERT OR REPLACE INTO seasons (playlist_id, series_id, season_number, name, overview, air_date, episode_count, cover)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            params![
+                playlist_id,
+                series_id,
+                season_number,
+                season.name,
+                season.overview,
+                season.air_date,
+                season.episode_count,
+                season.cover_big.or(season.cover),
+            ],
+        )?;
+    }
+
+    for episode in info.episodes {
+        let stream_url = client.episode_url(&episode.id, episode.container_extension.as_deref());
+        tx.execute(
+            &format!(
+                "INSERT OR REPLACE INTO episodes (playlist_id, series_id, {})
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15)",
+                EPISODE_COLUMNS
+            ),
+            params![
+                playlist_id,
+                series_id,
+                episode.id,
+                episode.season,
+                episode.episode_num,
+                episode.title,
+                episode.info.duration_secs,
+                episode.info.duration,
+                episode.info.plot,
+                episode.info.movie_image,
+                episode.info.rating,
+                episode.info.release_date,
+                episode.container_extension,
+                stream_url,
+                episode.added,
+            ],
+        )?;
+    }
+
+    tx.execute(
+        "INSERT OR REPLACE INTO series_info (playlist_id, series_id, fetched_at) VALUES (?1, ?2, ?3)",
+        params![playlist_id, series_id, fetched_at],
+    )?;
+    tx.commit()?;
+    Ok(())
+}
+
+fn is_fresh(details: &SeriesDetails, now: DateTime<Utc>) -> bool {
+    DateTime::parse_from_rfc3339(&details.fetched_at)
+        .map(|fetched_at| {
+            now < fetched_at.with_timezone(&Utc) + Duration::hours(SERIES_INFO_TTL_HOURS)
+        })
+        .unwrap_or(false)
+}
+
+/// Returns the seasons and episodes of a series, calling `get_series_info`
+/// when the cache is missing, older than `SERIES_INFO_TTL_HOURS` or
+/// `force_refresh` is set. If the provider fails, expired details are
+/// returned marked `stale` rather than an error.
+#[tauri::command(rename_all = "camelCase")]
+pub async fn get_series_details(
+    db: State<'_, DbConnection>,
+    playlist_id: i64,
+    series_id: String,
+    force_refresh: Option<bool>,
+) -> Result<SeriesDetails, Error> {
+    let (playlist, cached) = {
+        let conn = db.0.lock().unwrap();
+        (
+            load_playlist(&conn, playlist_id)?,
+            load_series_details(&conn, playlist_id, &series_id)?,
+        )
+    };
+
+    if let Some(cached) = &cached {
+        if !force_refresh.unwrap_or(false) && is_fresh(cached, Utc::now()) {
+            return Ok(cached.clone());
+        }
+    }
+
+    if playlist.source_type.unwrap_or_default() != SourceType::Xtream {
+        return Err(ValidationError::NotXtream("series details").into());
+    }
+
+    let client = XtreamClient::new(&playlist.server_url, &playlist.username, &playlist.password);
+    let info = match client.get_series_info(&series_id).await {
+        Ok(info) => info,
+        Err(e) => match cached {
+            Some(cached) => {
+                println!(
+                    "get_series_info failed for series {}, serving cached details: {}",
+                    series_id, e
+                );
+                return Ok(SeriesDetails {
+                    stale: true,
+                    ..cached
+                });
+            }
+            None => return Err(e),
+        },
+    };
+
+    let mut conn = db.0.lock().unwrap();
+    let fetched_at = Utc::now().to_rfc3339();
+    save_series_info(
+        &mut conn,
+        &client,
+        playlist_id,
+        &series_id,
+        info,
+        &fetched_at,
+    )?;
+    load_series_details(&conn, playlist_id, &series_id)?
+        .ok_or_else(|| Error::Internal(format!("Series {} was not cached", series_id)))
+}
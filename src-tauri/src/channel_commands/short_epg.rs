@@ -0,0 +1,294 @@
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use chrono::{NaiveDateTime, TimeZone, Utc};
+use chrono_tz::Tz;
+use rusqlite::{params, Connection, OptionalExtension};
+use tauri::State;
+
+use crate::account_commands::load_account_status;
+use crate::channel_commands::epg_commands::{programme_from_row, EPG_PROGRAMME_COLUMNS};
+use crate::channel_commands::epg_queries::GUIDE_CHANNEL_KEY;
+use crate::channel_commands::xtream_client::XtreamClient;
+use crate::channel_commands::xtream_models::ShortEpgListing;
+use crate::db::DbConnection;
+use crate::models::{EpgProgramme, Error, SourceType, ValidationError};
+use crate::playlist_commands::load_playlist;
+
+/// Listings asked of `get_short_epg` when the caller gives no limit: enough
+/// for now/next with some slack for programmes that already ended.
+const DEFAULT_SHORT_EPG_LIMIT: u32 = 4;
+
+/// How long cached listings are kept after they end. XMLTV imports leave
+/// them alone, so nothing else removes them.
+const CACHE_RETENTION_SECS: i64 = 24 * 60 * 60;
+
+/// The `epg_data` channel a stream's listings are cached under, the same
+/// one `get_now_next` and `get_epg_grid` read.
+fn guide_channel_id(conn: &Connection, playlist_id: i64, stream_id: &str) -> Result<String, Error> {
+    let guide_channel_id: Option<String> = conn
+        .query_row(
+            &format!(
+                "SELECT {} FROM channels c
+                 WHERE c.playlist_id = ?1 AND c.stream_id = ?2 AND COALESCE(c.content_type, 'live') = 'live'",
+                GUIDE_CHANNEL_KEY
+            ),
+            params![playlist_id, stream_id],
+            |row| row.get(0),
+        )
+        .optional()?;
+    Ok(guide_channel_id.unwrap_or_else(|| stream_id.to_string()))
+}
+
+/// Titles and descriptions are base64 on the panels we know of; a value that
+/// does not decode to text is kept as sent.
+fn decode_text(value: Option<String>) -> Option<String> {
+    let value = value?;
+    let text = STANDARD
+        .decode(value.trim())
+        .ok()
+        .and_then(|bytes| String::from_utf8(bytes).ok())
+        .unwrap_or(value);
+    let text = text.trim();
+    (!text.is_empty()).then(|| text.to_string())
+}
+
+/// Reads a `start` or `end` value such as `2024-01-31 20:30:00`, which is in
+/// the server's `timezone`. Without one it is taken as UTC; times that the
+/// timezone skips when clocks go forward are dropped.
+fn parse_listing_time(value: &str, timezone: Option<Tz>) -> Option<i64> {
+    let naive = NaiveDateTime::parse_from_str(value.trim(), "%Y-%m-%d %H:%M:%S").ok()?;
+    match timezone {
+        Some(timezone) => timezone
+            .from_local_datetime(&naive)
+            .earliest()
+            .map(|time| time.timestamp()),
+        None => Some(Utc.from_utc_datetime(&naive).timestamp()),
+    }
+}
+
+/// The server timezone from the playlist's last account check, which
+/// `start` and `end` of listings are given in.
+fn server_timezone(conn: &Connection, playlist_id: i64) -> Result<Option<Tz>, Error> {
+    Ok(load_account_status(conn, playlist_id)?
+        .and_then(|status| status.server_timezone)
+        .and_then(|timezone| timezone.trim().parse().ok()))
+}
+
+/// Converts a listing into a programme of `channel_id`. Listings without a
+/// time or title are dropped.
+fn listing_programme(
+    listing: ShortEpgListing,
+    channel_id: &str,
+    timezone: Option<Tz>,
+) -> Option<EpgProgramme> {
+    let start = listing
+        .start_timestamp
+        .or_else(|| parse_listing_time(listing.start.as_deref()?, timezone))?;
+    let end = listing
+        .stop_timestamp
+        .or_else(|| parse_listing_time(listing.end.as_deref()?, timezone))?;
+    Some(EpgProgramme {
+        channel_id: channel_id.to_string(),
+        start,
+        end,
+        title: decode_text(listing.title)?,
+        description: decode_text(listing.description),
+        ..Default::default()
+    })
+}
+
+fn cache_programmes(
+    conn: &mut Connection,
+    playlist_id: i64,
+    programmes: &[EpgProgramme],
+) -> Result<(), Error> {
+    let now = Utc::now();
+    let import_id = now.timestamp_millis();
+    let tx = conn.transaction()?;
+    tx.execute(
+        "DELETE FROM epg_data WHERE playlist_id = ?1 AND origin = 'short_epg' AND end < ?2",
+        params![playlist_id, now.timestamp() - CACHE_RETENTION_SECS],
+    )?;
+    {
+        // Only the fields the listings carry. XMLTV details of the same
+        // programme are kept, and so are the row's import and origin, so an
+        // XMLTV import still owns the rows it wrote
+        let mut insert_programme = tx.prepare(
+            "INSERT INTO epg_data (playlist_id, channel_id, start, end, title, description, import_id, origin)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, 'short_epg')
+             ON CONFLICT(playlist_id, channel_id, start) DO UPDATE SET
+                 end = excluded.end, title = excluded.title,
+                 description = COALESCE(excluded.description, description)",
+        )?;
+        for programme in programmes {
+            insert_programme.execute(params![
+                playlist_id,
+                programme.channel_id,
+                programme.start,
+                programme.end,
+                programme.title,
+                programme.description,
+                import_id,
+            ])?;
+        }
+    }
+    tx.commit()?;
+    Ok(())
+}
+
+/// Cached programmes of a channel that have not ended yet.
+fn load_upcoming(
+    conn: &Connection,
+    playlist_id: i64,
+    channel_id: &str,
+    limit: Option<u32>,
+) -> Result<Vec<EpgProgramme>, Error> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM epg_data WHERE playlist_id = ?1 AND channel_id = ?2 AND end > ?3
+         ORDER BY start LIMIT ?4",
+        EPG_PROGRAMME_COLUMNS
+    ))?;
+    let limit = limit.map(i64::from).unwrap_or(-1);
+    let programmes = stmt
+        .query_map(
+            params![playlist_id, channel_id, Utc::now().timestamp(), limit],
+            programme_from_row,
+        )?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(programmes)
+}
+
+/// Fetches the listings of one live stream with `get_short_epg`, or the
+/// whole table with `get_simple_data_table` when `full_day` is set, decodes
+/// them and caches them in `epg_data`. For providers without a usable XMLTV
+/// guide this is the only source of now/next. If the provider fails, the
+/// cached programmes that have not ended are returned instead.
+#[tauri::command(rename_all = "camelCase")]
+pub async fn get_short_epg(
+    db: State<'_, DbConnection>,
+    playlist_id: i64,
+    stream_id: String,
+    limit: Option<u32>,
+    full_day: Option<bool>,
+) -> Result<Vec<EpgProgramme>, Error> {
+    let (playlist, channel_id, timezone) = {
+        let conn = db.0.lock().unwrap();
+        (
+            load_playlist(&conn, playlist_id)?,
+            guide_channel_id(&conn, playlist_id, &stream_id)?,
+            server_timezone(&conn, playlist_id)?,
+        )
+    };
+    if playlist.source_type.unwrap_or_default() != SourceType::Xtream {
+        return Err(ValidationError::NotXtream("the short EPG").into());
+    }
+
+    let client = XtreamClient::new(&playlist.server_url, &playlist.username, &playlist.password);
+    let full_day = full_day.unwrap_or(false);
+    let limit = limit.unwrap_or(DEFAULT_SHORT_EPG_LIMIT);
+    let fetched = if full_day {
+        client.get_simple_data_table(&stream_id).await
+    } else {
+        client.get_short_epg(&stream_id, limit).await
+    };
+
+    let mut conn = db.0.lock().unwrap();
+    let listings = match fetched {
+        Ok(epg) => epg.epg_listings,
+        Err(e) => {
+            println!(
+                "Short EPG failed for stream {}, serving cached programmes: {}",
+                stream_id, e
+            );
+            let cached = load_upcoming(
+                &conn,
+                playlist_id,
+                &channel_id,
+                (!full_day).then_some(limit),
+            )?;
+            if cached.is_empty() {
+                return Err(e);
+            }
+            return Ok(cached);
+        }
+    };
+
+    let mut programmes: Vec<EpgProgramme> = listings
+        .into_iter()
+        .filter_map(|listing| listing_programme(listing, &channel_id, timezone))
+        .collect();
+    programmes.sort_by_key(|programme| programme.start);
+    cache_programmes(&mut conn, playlist_id, &programmes)?;
+    println!(
+        "Cached {} short EPG programmes for stream {}",
+        programmes.len(),
+        stream_id
+    );
+    Ok(programmes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn listing(start: &str, end: &str, title: &str) -> ShortEpgListing {
+        ShortEpgListing {
+            start: Some(start.to_string()),
+            end: Some(end.to_string()),
+            title: Some(title.to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn decodes_base64_text() {
+        assert_eq!(
+            decode_text(Some("IE5ld3MgYXQgVGVuIA==".to_string())).as_deref(),
+            Some("News at Ten")
+        );
+        // Not base64, or not text once decoded
+        assert_eq!(
+            decode_text(Some("News at Ten".to_string())).as_deref(),
+            Some("News at Ten")
+        );
+        assert_eq!(
+            decode_text(Some("News".to_string())).as_deref(),
+            Some("News")
+        );
+        assert_eq!(decode_text(Some("ICA=".to_string())), None);
+        assert_eq!(decode_text(Some(" ".to_string())), None);
+        assert_eq!(decode_text(None), None);
+    }
+
+    #[test]
+    fn reads_listing_times_in_the_server_timezone() {
+        let winter = || listing("2024-01-31 20:30:00", "2024-01-31 21:00:00", "TmV3cw==");
+        let programme = listing_programme(winter(), "bbc", None).unwrap();
+        assert_eq!((programme.start, programme.end), (1706733000, 1706734800));
+        assert_eq!(programme.channel_id, "bbc");
+        assert_eq!(programme.title, "News");
+
+        let berlin = "Europe/Berlin".parse().ok();
+        let programme = listing_programme(winter(), "bbc", berlin).unwrap();
+        assert_eq!((programme.start, programme.end), (1706729400, 1706731200));
+
+        // Summer time, UTC+2
+        let summer = listing("2024-07-01 20:30:00", "2024-07-01 21:00:00", "TmV3cw==");
+        let programme = listing_programme(summer, "bbc", berlin).unwrap();
+        assert_eq!(programme.start, 1719858600);
+    }
+
+    #[test]
+    fn prefers_timestamps_and_drops_incomplete_listings() {
+        let mut with_timestamps = listing("2024-01-31 20:30:00", "bogus", "TmV3cw==");
+        with_timestamps.start_timestamp = Some(100);
+        with_timestamps.stop_timestamp = Some(200);
+        let programme = listing_programme(with_timestamps, "bbc", None).unwrap();
+        assert_eq!((programme.start, programme.end), (100, 200));
+
+        let bad_time = listing("2024-01-31 20:30:00", "bogus", "TmV3cw==");
+        assert!(listing_programme(bad_time, "bbc", None).is_none());
+        let no_title = listing("2024-01-31 20:30:00", "2024-01-31 21:00:00", "");
+        assert!(listing_programme(no_title, "bbc", None).is_none());
+    }
+}
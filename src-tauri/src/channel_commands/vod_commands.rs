@@ -0,0 +1,318 @@
+use chrono::Utc;
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use tauri::{AppHandle, Emitter, Runtime, State};
+
+use crate::channel_commands::import_registry::ImportRegistry;
+use crate::channel_commands::xtream_client::XtreamClient;
+use crate::channel_commands::xtream_models::VodInfoDetails;
+use crate::db::DbConnection;
+use crate::models::{
+    EnrichmentProgress, EnrichmentReport, Error, MovieDetails, Playlist, SourceType,
+    ValidationError,
+};
+use crate::playlist_commands::load_playlist;
+
+/// How long fetched movie details are served before asking again.
+const VOD_INFO_TTL_DAYS: i64 = 7;
+
+/// Movies fetched concurrently by `enrich_vod_metadata`; the client's
+/// per-provider limit still applies.
+const ENRICHMENT_BATCH: usize = 3;
+
+/// Event emitted with an `EnrichmentProgress` payload during `enrich_vod_metadata`.
+pub const ENRICHMENT_PROGRESS_EVENT: &str = "vod-enrichment-progress";
+
+/// Catalogue enrichments currently running, keyed by playlist ID. Kept apart
+/// from imports so enriching does not block a refresh of the same playlist.
+#[derive(Default)]
+pub struct EnrichmentRegistry(pub ImportRegistry);
+
+const MOVIE_COLUMNS: &str = "c.stream_id, c.name, c.stream_url, c.container_extension, c.cover, c.stream_icon, c.plot, c.\"cast\", c.director, c.genre, c.release_date, c.rating_5based, c.backdrop_path, c.youtube_trailer, m.original_name, m.plot, m.\"cast\", m.director, m.genre, m.country, m.release_date, m.year, m.rating, m.duration_secs, m.duration, m.imdb_id, m.tmdb_id, m.cover, m.backdrop_path, m.youtube_trailer, m.video, m.audio, m.bitrate, m.updated_at";
+
+fn json_column<T: serde::de::DeserializeOwned>(value: Option<String>) -> Option<T> {
+    value.and_then(|value| serde_json::from_str(&value).ok())
+}
+
+/// Builds the details from a `MOVIE_COLUMNS` row, preferring `get_vod_info`
+/// values and falling back to what the stream list provided.
+fn movie_from_row(playlist_id: i64, row: &Row) -> rusqlite::Result<MovieDetails> {
+    let list_rating: Option<f64> = row.get(11)?;
+    let list_backdrops: Option<String> = row.get(12)?;
+    let backdrops: Option<String> = row.get(28)?;
+    Ok(MovieDetails {
+        playlist_id,
+        stream_id: row.get(0)?,
+        name: row.get(1)?,
+        stream_url: row.get(2)?,
+        container_extension: row.get(3)?,
+        original_name: row.get(14)?,
+        plot: row.get::<_, Option<String>>(15)?.or(row.get(6)?),
+        cast: row.get::<_, Option<String>>(16)?.or(row.get(7)?),
+        director: row.get::<_, Option<String>>(17)?.or(row.get(8)?),
+        genre: row.get::<_, Option<String>>(18)?.or(row.get(9)?),
+        country: row.get(19)?,
+        release_date: row.get::<_, Option<String>>(20)?.or(row.get(10)?),
+        year: row.get(21)?,
+        // The stream list rates out of 5, get_vod_info out of 10
+        rating: row
+            .get::<_, Option<f64>>(22)?
+            .or(list_rating.map(|rating| rating * 2.0)),
+        duration_secs: row.get(23)?,
+        duration: row.get(24)?,
+        imdb_id: row.get(25)?,
+        tmdb_id: row.get(26)?,
+        cover: row
+            .get::<_, Option<String>>(27)?
+            .or(row.get(4)?)
+            .or(row.get(5)?),
+        backdrop_path: json_column(backdrops)
+            .or_else(|| json_column(list_backdrops))
+            .unwrap_or_default(),
+        youtube_trailer: row.get::<_, Option<String>>(29)?.or(row.get(13)?),
+        video: json_column(row.get(30)?),
+        audio: json_column(row.get(31)?),
+        bitrate: row.get(32)?,
+        updated_at: row.get(33)?,
+    })
+}
+
+fn load_movie(
+    conn: &Connection,
+    playlist_id: i64,
+    stream_id: &str,
+) -> Result<Option<MovieDetails>, Error> {
+    let movie = conn
+        .query_row(
+            &format!(
+                "SELECT {} FROM channels c
+                 LEFT JOIN vod_metadata m ON m.playlist_id = c.playlist_id AND m.stream_id = c.stream_id
+                 WHERE c.playlist_id = ?1 AND c.stream_id = ?2 AND c.content_type = 'movie'",
+                MOVIE_COLUMNS
+            ),
+            params![playlist_id, stream_id],
+            |row| movie_from_row(playlist_id, row),
+        )
+        .optional()?;
+    Ok(movie)
+}
+
+/// The release year, from dates like `2019-05-24` or a bare `2019`.
+fn release_year(release_date: Option<&str>) -> Option<i64> {
+    let year = release_date?.trim().get(..4)?;
+    year.parse().ok().filter(|year| *year > 1800)
+}
+
+fn save_vod_metadata(
+    conn: &Connection,
+    playlist_id: i64,
+    stream_id: &str,
+    info: &VodInfoDetails,
+) -> Result<(), Error> {
+    let to_json = |value: Option<&serde_json::Value>| {
+        value
+            .filter(|value| value.is_object())
+            .map(|value| value.to_string())
+    };
+    let backdrops =
+        (!info.backdrop_path.is_empty()).then(|| serde_json::to_string(&info.backdrop_path));
+    conn.execute(
+        "INSERT INTO vod_metadata (playlist_id, stream_id, rating, director, year, plot, imdb_id, tmdb_id, original_name, duration_secs, duration, genre, \"cast\", country, release_date, cover, backdrop_path, youtube_trailer, video, audio, bitrate)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19, ?20, ?21)
+         ON CONFLICT(playlist_id, stream_id) DO UPDATE SET
+             rating = excluded.rating, director = excluded.director, year = excluded.year,
+             plot = excluded.plot, imdb_id = excluded.imdb_id, tmdb_id = excluded.tmdb_id,
+             original_name = excluded.original_name, duration_secs = excluded.duration_secs,
+             duration = excluded.duration, genre = excluded.genre, \"cast\" = excluded.\"cast\",
+             country = excluded.country, release_date = excluded.release_date,
+             cover = excluded.cover, backdrop_path = excluded.backdrop_path,
+             youtube_trailer = excluded.youtube_trailer, video = excluded.video,
+             audio = excluded.audio, bitrate = excluded.bitrate,
+             updated_at = strftime('%s', 'now')",
+        params![
+            playlist_id,
+            stream_id,
+            info.rating,
+            info.director,
+            release_year(info.release_date.as_deref()),
+            info.plot.as_ref().or(info.description.as_ref()),
+            info.imdb_id,
+            info.tmdb_id,
+            info.o_name,
+            info.duration_secs,
+            info.duration,
+            info.genre,
+            info.cast.as_ref().or(info.actors.as_ref()),
+            info.country,
+            info.release_date,
+            info.cover_big.as_ref().or(info.movie_image.as_ref()),
+            backdrops.transpose()?,
+            info.youtube_trailer,
+            to_json(info.video.as_ref()),
+            to_json(info.audio.as_ref()),
+            info.bitrate,
+        ],
+    )?;
+    Ok(())
+}
+
+fn xtream_client(playlist: &Playlist) -> Result<XtreamClient, Error> {
+    if playlist.source_type.unwrap_or_default() != SourceType::Xtream {
+        return Err(ValidationError::NotXtream("movie details").into());
+    }
+    Ok(XtreamClient::new(
+        &playlist.server_url,
+        &playlist.username,
+        &playlist.password,
+    ))
+}
+
+/// Returns a movie's details, calling `get_vod_info` when they were never
+/// fetched, are older than `VOD_INFO_TTL_DAYS` or `force_refresh` is set.
+/// If the provider fails, whatever is stored is returned instead.
+#[tauri::command(rename_all = "camelCase")]
+pub async fn get_movie_details(
+    db: State<'_, DbConnection>,
+    playlist_id: i64,
+    stream_id: String,
+    force_refresh: Option<bool>,
+) -> Result<MovieDetails, Error> {
+    let (playlist, stored) = {
+        let conn = db.0.lock().unwrap();
+        let stored = load_movie(&conn, playlist_id, &stream_id)?.ok_or_else(|| {
+            Error::Internal(format!(
+                "Movie {} not found in playlist {}",
+                stream_id, playlist_id
+            ))
+        })?;
+        (load_playlist(&conn, playlist_id)?, stored)
+    };
+
+    let fresh = matches!(
+        stored.updated_at,
+        Some(updated_at) if Utc::now().timestamp() < updated_at + VOD_INFO_TTL_DAYS * 86_400
+    );
+    if fresh && !force_refresh.unwrap_or(false) {
+        return Ok(stored);
+    }
+
+    let client = xtream_client(&playlist)?;
+    match client.get_vod_info(&stream_id).await {
+        Ok(info) => {
+            let conn = db.0.lock().unwrap();
+            save_vod_metadata(&conn, playlist_id, &stream_id, &info.info)?;
+            Ok(load_movie(&conn, playlist_id, &stream_id)?.unwrap_or(stored))
+        }
+        Err(e) => {
+            println!(
+                "get_vod_info failed for movie {}, serving stored details: {}",
+                stream_id, e
+            );
+            Ok(stored)
+        }
+    }
+}
+
+/// Fetches `get_vod_info` for every movie of the playlist that has no
+/// details yet, emitting `ENRICHMENT_PROGRESS_EVENT` as it goes. Meant to be
+/// started without awaiting; `cancel_vod_enrichment` stops it between batches.
+#[tauri::command(rename_all = "camelCase")]
+pub async fn enrich_vod_metadata<R: Runtime>(
+    app_handle: AppHandle<R>,
+    db: State<'_, DbConnection>,
+    enrichments: State<'_, EnrichmentRegistry>,
+    playlist_id: i64,
+) -> Result<EnrichmentReport, Error> {
+    let (playlist, pending) = {
+        let conn = db.0.lock().unwrap();
+        let playlist = load_playlist(&conn, playlist_id)?;
+        let mut stmt = conn.prepare(
+            "SELECT c.stream_id FROM channels c
+             LEFT JOIN vod_metadata m ON m.playlist_id = c.playlist_id AND m.stream_id = c.stream_id
+             WHERE c.playlist_id = ?1 AND c.content_type = 'movie' AND m.id IS NULL
+             ORDER BY c.id",
+        )?;
+        let pending = stmt
+            .query_map([playlist_id], |row| row.get::<_, String>(0))?
+            .collect::<Result<Vec<_>, _>>()?;
+        (playlist, pending)
+    };
+    let client = xtream_client(&playlist)?;
+    let guard = enrichments.0.start(playlist_id)?;
+
+    let mut report = EnrichmentReport {
+        playlist_id,
+        total: pending.len(),
+        ..Default::default()
+    };
+    println!(
+        "Enriching {} movies of playlist {}",
+        pending.len(),
+        playlist_id
+    );
+
+    for (batch_index, batch) in pending.chunks(ENRICHMENT_BATCH).enumerate() {
+        guard.handle.check()?;
+        let fetches: Vec<_> = batch
+            .iter()
+            .map(|stream_id| {
+                let client = client.clone();
+                let stream_id = stream_id.clone();
+                tokio::spawn(async move {
+                    let info = client.get_vod_info(&stream_id).await;
+                    (stream_id, info)
+                })
+            })
+            .collect();
+
+        let mut results = Vec::with_capacity(fetches.len());
+        for fetch in fetches {
+            match fetch.await {
+                Ok(result) => results.push(result),
+                Err(e) => {
+                    println!("Movie details task failed: {}", e);
+                    report.failed += 1;
+                }
+            }
+        }
+
+        {
+            let conn = db.0.lock().unwrap();
+            for (stream_id, info) in results {
+                match info {
+                    Ok(info) => {
+                        save_vod_metadata(&conn, playlist_id, &stream_id, &info.info)?;
+                        report.enriched += 1;
+                    }
+                    Err(e) => {
+                        println!("get_vod_info failed for movie {}: {}", stream_id, e);
+                        report.failed += 1;
+                    }
+                }
+            }
+        }
+
+        let progress = EnrichmentProgress {
+            playlist_id,
+            current: (batch_index * ENRICHMENT_BATCH + batch.len()).min(report.total),
+            total: report.total,
+        };
+        if let Err(e) = app_handle.emit(ENRICHMENT_PROGRESS_EVENT, progress) {
+            println!("Failed to emit enrichment progress: {}", e);
+        }
+    }
+
+    println!(
+        "Enrichment of playlist {} finished: {} enriched, {} failed",
+        playlist_id, report.enriched, report.failed
+    );
+    Ok(report)
+}
+
+/// Stops a running `enrich_vod_metadata`. Returns false if none is running.
+#[tauri::command(rename_all = "camelCase")]
+pub async fn cancel_vod_enrichment(
+    enrichments: State<'_, EnrichmentRegistry>,
+    playlist_id: i64,
+) -> Result<bool, Error> {
+    Ok(enrichments.0.cancel(playlist_id))
+}
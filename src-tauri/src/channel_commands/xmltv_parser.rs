@@ -0,0 +1,386 @@
+use chrono::{DateTime, FixedOffset, NaiveDateTime, TimeZone};
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::Reader;
+use std::io::BufRead;
+
+use crate::models::Error;
+
+/// A `<channel>` element of an XMLTV guide.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct XmltvChannel {
+    pub id: String,
+    /// Every `<display-name>`, in document order.
+    pub display_names: Vec<String>,
+    pub icon: Option<String>,
+}
+
+/// A `<programme>` element of an XMLTV guide. Times are Unix seconds.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct XmltvProgramme {
+    pub channel_id: String,
+    pub start: i64,
+    pub end: i64,
+    pub title: String,
+    pub sub_title: Option<String>,
+    pub description: Option<String>,
+    /// `<category>` values joined with `, `.
+    pub category: Option<String>,
+    pub icon: Option<String>,
+    /// The `<episode-num>` text as given, preferring `xmltv_ns`.
+    pub episode_num: Option<String>,
+    /// One-based season and episode, when `episode-num` could be read.
+    pub season: Option<i64>,
+    pub episode: Option<i64>,
+}
+
+pub enum XmltvItem {
+    Channel(XmltvChannel),
+    Programme(XmltvProgramme),
+}
+
+/// Parses an XMLTV timestamp such as `20240131203000 +0100`. Seconds and
+/// the offset are optional; a missing offset means UTC.
+pub fn parse_xmltv_time(value: &str) -> Option<i64> {
+    let value = value.trim();
+    let (digits, offset) = match value.split_once(' ') {
+        Some((digits, offset)) => (digits, Some(offset.trim())),
+        None => (value, None),
+    };
+    let digits = match digits.len() {
+        12 => format!("{}00", digits),
+        len if len >= 14 => digits.get(..14)?.to_string(),
+        _ => return None,
+    };
+    let naive = NaiveDateTime::parse_from_str(&digits, "%Y%m%d%H%M%S").ok()?;
+
+    let offset_seconds = match offset {
+        Some(offset) if offset.len() == 5 => {
+            let (sign, hhmm) = match offset.strip_prefix('+') {
+                Some(hhmm) => (1, hhmm),
+                None => (-1, offset.strip_prefix('-')?),
+            };
+            let hours: i32 = hhmm.get(..2)?.parse().ok()?;
+            let minutes: i32 = hhmm.get(2..)?.parse().ok()?;
+            sign * (hours * 3600 + minutes * 60)
+        }
+        _ => 0,
+    };
+    let offset = FixedOffset::east_opt(offset_seconds)?;
+    let time: DateTime<FixedOffset> = offset.from_local_datetime(&naive).single()?;
+    Some(time.timestamp())
+}
+
+/// Reads one-based season and episode numbers from an `episode-num` value.
+/// `xmltv_ns` is zero-based `season.episode.part` with optional `/total`;
+/// `onscreen` looks like `S01E05`.
+pub fn parse_episode_num(system: Option<&str>, value: &str) -> (Option<i64>, Option<i64>) {
+    let value = value.trim();
+    if system == Some("xmltv_ns") {
+        let mut parts = value.split('.');
+        let mut number = || {
+            parts
+                .next()
+                .and_then(|part| part.split('/').next())
+                .and_then(|part| part.trim().parse::<i64>().ok())
+                .map(|n| n + 1)
+        };
+        let season = number();
+        let episode = number();
+        return (season, episode);
+    }
+
+    let upper = value.to_ascii_uppercase();
+    let Some(rest) = upper.strip_prefix('S') else {
+        return (None, None);
+    };
+    match rest.split_once('E') {
+        Some((season, episode)) => {
+            let episode: String = episode.chars().take_while(char::is_ascii_digit).collect();
+            (season.trim().parse().ok(), episode.parse().ok())
+        }
+        None => (rest.trim().parse().ok(), None),
+    }
+}
+
+fn attribute(element: &BytesStart, name: &str) -> Result<Option<String>, Error> {
+    Ok(match element.try_get_attribute(name)? {
+        Some(attr) => Some(attr.unescape_value()?.trim().to_string()),
+        None => None,
+    })
+}
+
+fn non_empty(value: String) -> Option<String> {
+    let value = value.trim();
+    (!value.is_empty()).then(|| value.to_string())
+}
+
+/// What the text inside the current element belongs to.
+#[derive(Clone, Copy, PartialEq)]
+enum Field {
+    None,
+    DisplayName,
+    Title,
+    SubTitle,
+    Description,
+    Category,
+    EpisodeNum,
+}
+
+/// Streams the guide in `source`, calling `on_item` for every channel and
+/// programme as soon as its closing tag is read, so memory use does not grow
+/// with the size of the guide. Programmes without a valid channel, start or
+/// stop are skipped and counted in the returned total.
+pub fn parse_xmltv<B: BufRead>(
+    source: B,
+    on_item: &mut dyn FnMut(XmltvItem) -> Result<(), Error>,
+) -> Result<usize, Error> {
+    let mut reader = Reader::from_reader(source);
+    reader.check_end_names(false);
+
+    let mut buf = Vec::new();
+    let mut channel: Option<XmltvChannel> = None;
+    let mut programme: Option<XmltvProgramme> = None;
+    let mut categories: Vec<String> = Vec::new();
+    let mut episode_system: Option<String> = None;
+    let mut episode_rank = 0;
+    let mut field = Field::None;
+    let mut text = String::new();
+    let mut skipped = 0;
+
+    loop {
+        let event = reader.read_event_into(&mut buf)?;
+        match event {
+            Event::Start(ref element) | Event::Empty(ref element) => {
+                let is_empty = matches!(event, Event::Empty(_));
+                match element.name().as_ref() {
+                    b"channel" if !is_empty => {
+                        channel = attribute(element, "id")?.map(|id| XmltvChannel {
+                            id,
+                            ..Default::default()
+                        });
+                    }
+                    b"programme" if !is_empty => {
+                        let channel_id = attribute(element, "channel")?.unwrap_or_default();
+                        let start = attribute(element, "start")?
+                            .as_deref()
+                            .and_then(parse_xmltv_time);
+                        let end = attribute(element, "stop")?
+                            .as_deref()
+                            .and_then(parse_xmltv_time);
+                        programme = match (start, end) {
+                            (Some(start), Some(end)) if !channel_id.is_empty() => {
+                                Some(XmltvProgramme {
+                                    channel_id,
+                                    start,
+                                    end,
+                                    ..Default::default()
+                                })
+                            }
+                            _ => {
+                                skipped += 1;
+                                None
+                            }
+                        };
+                        categories.clear();
+                        episode_rank = 0;
+                    }
+                    b"icon" => {
+                        let src = attribute(element, "src")?.and_then(non_empty);
+                        if let Some(programme) = programme.as_mut() {
+                            programme.icon = programme.icon.take().or(src);
+                        } else if let Some(channel) = channel.as_mut() {
+                            channel.icon = channel.icon.take().or(src);
+                        }
+                    }
+                    name if !is_empty => {
+                        field = match name {
+                            b"display-name" if channel.is_some() => Field::DisplayName,
+                            b"title" if programme.is_some() => Field::Title,
+                            b"sub-title" if programme.is_some() => Field::SubTitle,
+                            b"desc" if programme.is_some() => Field::Description,
+                            b"category" if programme.is_some() => Field::Category,
+                            b"episode-num" if programme.is_some() => {
+                                episode_system = attribute(element, "system")?;
+                                Field::EpisodeNum
+                            }
+                            _ => Field::None,
+                        };
+                        text.clear();
+                    }
+                    _ => {}
+                }
+            }
+            Event::Text(ref content) if field != Field::None => {
+                text.push_str(&content.unescape()?);
+            }
+            Event::CData(ref content) if field != Field::None => {
+                text.push_str(&String::from_utf8_lossy(content));
+            }
+            Event::End(ref element) => match element.name().as_ref() {
+                b"channel" => {
+                    if let Some(channel) = channel.take() {
+                        on_item(XmltvItem::Channel(channel))?;
+                    }
+                }
+                b"programme" => {
+                    if let Some(mut programme) = programme.take() {
+                        if !categories.is_empty() {
+                            programme.category = Some(categories.join(", "));
+                        }
+                        on_item(XmltvItem::Programme(programme))?;
+                    }
+                }
+                _ => {
+                    let value = non_empty(std::mem::take(&mut text));
+                    match (field, value) {
+                        (Field::DisplayName, Some(value)) => {
+                            if let Some(channel) = channel.as_mut() {
+                                channel.display_names.push(value);
+                            }
+                        }
+                        (Field::Category, Some(value)) => categories.push(value),
+                        (field, Some(value)) => {
+                            if let Some(programme) = programme.as_mut() {
+                                match field {
+                                    // Guides may repeat these per language; keep the first
+                                    Field::Title if programme.title.is_empty() => {
+                                        programme.title = value
+                                    }
+                                    Field::SubTitle if programme.sub_title.is_none() => {
+                                        programme.sub_title = Some(value)
+                                    }
+                                    Field::Description if programme.description.is_none() => {
+                                        programme.description = Some(value)
+                                    }
+                                    Field::EpisodeNum => {
+                                        let rank = match episode_system.as_deref() {
+                                            Some("xmltv_ns") => 2,
+                                            Some("onscreen") => 1,
+                                            _ => 0,
+                                        };
+                                        if programme.episode_num.is_none() || rank > episode_rank {
+                                            let (season, episode) = parse_episode_num(
+                                                episode_system.as_deref(),
+                                                &value,
+                                            );
+                                            programme.season = season;
+                                            programme.episode = episode;
+                                            programme.episode_num = Some(value);
+                                            episode_rank = rank;
+                                        }
+                                    }
+                                    _ => {}
+                                }
+                            }
+                        }
+                        _ => {}
+                    }
+                    field = Field::None;
+                }
+            },
+            Event::Eof => break,
+            _ => {}
+        }
+        buf.clear();
+    }
+
+    Ok(skipped)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(xml: &str) -> (Vec<XmltvChannel>, Vec<XmltvProgramme>, usize) {
+        let mut channels = Vec::new();
+        let mut programmes = Vec::new();
+        let skipped = parse_xmltv(xml.as_bytes(), &mut |item| {
+            match item {
+                XmltvItem::Channel(channel) => channels.push(channel),
+                XmltvItem::Programme(programme) => programmes.push(programme),
+            }
+            Ok(())
+        })
+        .unwrap();
+        (channels, programmes, skipped)
+    }
+
+    #[test]
+    fn parses_times_with_offsets() {
+        assert_eq!(parse_xmltv_time("20240101120000 +0000"), Some(1704110400));
+        assert_eq!(parse_xmltv_time("20240101130000 +0100"), Some(1704110400));
+        assert_eq!(parse_xmltv_time("20240101070000 -0500"), Some(1704110400));
+        assert_eq!(parse_xmltv_time("202401011200"), Some(1704110400));
+        assert_eq!(parse_xmltv_time("2024"), None);
+    }
+
+    #[test]
+    fn rejects_times_with_multibyte_characters() {
+        assert_eq!(parse_xmltv_time("2024010112000é"), None);
+        assert_eq!(parse_xmltv_time("20240101120000 é000"), None);
+        assert_eq!(parse_xmltv_time("20240101120000 +0é0"), None);
+        assert_eq!(parse_xmltv_time("20240101120000 +01é"), None);
+    }
+
+    #[test]
+    fn parses_episode_numbers() {
+        assert_eq!(
+            parse_episode_num(Some("xmltv_ns"), "1.4.0/1"),
+            (Some(2), Some(5))
+        );
+        assert_eq!(parse_episode_num(Some("xmltv_ns"), ".4."), (None, Some(5)));
+        assert_eq!(
+            parse_episode_num(Some("onscreen"), "S02E05"),
+            (Some(2), Some(5))
+        );
+        assert_eq!(parse_episode_num(None, "Episode 5"), (None, None));
+    }
+
+    #[test]
+    fn parses_channels_and_programmes() {
+        let (channels, programmes, skipped) = parse(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE tv SYSTEM "xmltv.dtd">
+<tv generator-info-name="test">
+  <channel id="bbc1.uk">
+    <display-name lang="en">BBC One</display-name>
+    <display-name>BBC 1</display-name>
+    <icon src="http://logo/bbc1.png" />
+  </channel>
+  <programme start="20240101120000 +0000" stop="20240101130000 +0000" channel="bbc1.uk">
+    <title lang="en">News &amp; Weather</title>
+    <title lang="de">Nachrichten</title>
+    <sub-title>Midday</sub-title>
+    <desc><![CDATA[Headlines <live>]]></desc>
+    <category>News</category>
+    <category>Current affairs</category>
+    <episode-num system="onscreen">S01E02</episode-num>
+    <episode-num system="xmltv_ns">2.9.</episode-num>
+    <icon src="http://img/news.jpg"/>
+  </programme>
+  <programme start="bad" stop="20240101130000 +0000" channel="bbc1.uk">
+    <title>Broken</title>
+  </programme>
+</tv>"#,
+        );
+        assert_eq!(
+            channels,
+            vec![XmltvChannel {
+                id: "bbc1.uk".to_string(),
+                display_names: vec!["BBC One".to_string(), "BBC 1".to_string()],
+                icon: Some("http://logo/bbc1.png".to_string()),
+            }]
+        );
+        assert_eq!(skipped, 1);
+        assert_eq!(programmes.len(), 1);
+        let programme = &programmes[0];
+        assert_eq!(programme.title, "News & Weather");
+        assert_eq!(programme.sub_title.as_deref(), Some("Midday"));
+        assert_eq!(programme.description.as_deref(), Some("Headlines <live>"));
+        assert_eq!(programme.category.as_deref(), Some("News, Current affairs"));
+        assert_eq!(programme.episode_num.as_deref(), Some("2.9."));
+        assert_eq!((programme.season, programme.episode), (Some(3), Some(10)));
+        assert_eq!(programme.icon.as_deref(), Some("http://img/news.jpg"));
+        assert_eq!(programme.end - programme.start, 3600);
+    }
+}
@@ -0,0 +1,395 @@
+use serde::de::DeserializeOwned;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, OnceLock};
+use tokio::sync::Semaphore;
+use url::Url;
+
+use crate::channel_commands::xtream_models::{
+    AccountInfo, LiveStream, Series, SeriesInfo, ShortEpg, VodInfo, VodStream, XtreamCategory,
+};
+use crate::models::{Channel, Error};
+
+/// Maximum number of requests in flight against a single provider, shared by
+/// every client talking to the same host so imports do not trip rate limits.
+const MAX_CONCURRENT_REQUESTS: usize = 3;
+
+fn provider_limit(server_url: &str) -> Arc<Semaphore> {
+    static LIMITS: OnceLock<Mutex<HashMap<String, Arc<Semaphore>>>> = OnceLock::new();
+
+    let key = Url::parse(server_url)
+        .ok()
+        .and_then(|url| {
+            url.host_str()
+                .map(|host| format!("{}:{}", host, url.port_or_known_default().unwrap_or(0)))
+        })
+        .unwrap_or_else(|| server_url.to_string());
+
+    LIMITS
+        .get_or_init(Default::default)
+        .lock()
+        .unwrap()
+        .entry(key)
+        .or_insert_with(|| Arc::new(Semaphore::new(MAX_CONCURRENT_REQUESTS)))
+        .clone()
+}
+
+/// Client for the `player_api.php` endpoints of an Xtream Codes server.
+#[derive(Clone)]
+pub struct XtreamClient {
+    client: reqwest::Client,
+    limit: Arc<Semaphore>,
+    server_url: String,
+    username: String,
+    password: String,
+    /// Extension of live stream URLs, `ts` or `m3u8`.
+    live_format: String,
+}
+
+/// Live output formats that work as a URL extension. Panels also list `rtmp`,
+/// which needs a different URL scheme altogether.
+const LIVE_FORMATS: [&str; 2] = ["ts", "m3u8"];
+
+/// Picks the live output format: the playlist's preference when the account
+/// allows it (or does not say), otherwise the first usable allowed format.
+/// Accounts that only allow formats with no URL extension get `ts`, which
+/// every panel serves.
+pub fn live_output_format(preferred: Option<&str>, allowed: &[String]) -> &'static str {
+    let normalize = |format: &str| match format.trim().to_ascii_lowercase().as_str() {
+        "hls" | "m3u8" => Some("m3u8"),
+        "ts" | "mpegts" => Some("ts"),
+        _ => None,
+    };
+    let usable: Vec<&str> = allowed
+        .iter()
+        .filter_map(|format| normalize(format))
+        .collect();
+
+    if let Some(preferred) = preferred.and_then(normalize) {
+        // An empty list means the account does not say
+        if allowed.is_empty() || usable.contains(&preferred) {
+            return preferred;
+        }
+        println!(
+            "Output format {} is not allowed by the account ({:?})",
+            preferred, allowed
+        );
+    }
+    if !allowed.is_empty() && usable.is_empty() {
+        println!(
+            "None of the account's output formats ({:?}) work as a URL extension, using ts",
+            allowed
+        );
+    }
+    LIVE_FORMATS
+        .into_iter()
+        .find(|format| usable.contains(format))
+        .unwrap_or("ts")
+}
+
+impl XtreamClient {
+    pub fn new(server_url: &str, username: &str, password: &str) -> Self {
+        let server_url = server_url.trim_end_matches('/').to_string();
+        XtreamClient {
+            client: reqwest::Client::new(),
+            limit: provider_limit(&server_url),
+            server_url,
+            username: username.to_string(),
+            password: password.to_string(),
+            live_format: "ts".to_string(),
+        }
+    }
+
+    /// Sets the extension used for live stream URLs, see `live_output_format`.
+    pub fn with_live_format(mut self, format: &str) -> Self {
+        self.live_format = format.to_string();
+        self
+    }
+
+    fn media_url(&self, kind: &str, stream_id: &str, extension: Option<&str>) -> String {
+        let mut url = format!(
+            "{}/{}/{}/{}/{}",
+            self.server_url, kind, self.username, self.password, stream_id
+        );
+        if let Some(extension) = extension.map(|e| e.trim_start_matches('.')) {
+            if !extension.is_empty() {
+                url.push('.');
+                url.push_str(extension);
+            }
+        }
+        url
+    }
+
+    /// Playback URL of a live stream in the client's live format.
+    pub fn live_stream_url(&self, stream_id: &str) -> String {
+        self.media_url("live", stream_id, Some(&self.live_format))
+    }
+
+    /// Playback URL of a movie; the file keeps its own container format.
+    pub fn movie_url(&self, stream_id: &str, container_extension: Option<&str>) -> String {
+        self.media_url(
+            "movie",
+            stream_id,
+            Some(container_extension.unwrap_or("mp4")),
+        )
+    }
+
+    /// Playback URL of a series episode, by the episode's own ID.
+    pub fn episode_url(&self, episode_id: &str, container_extension: Option<&str>) -> String {
+        self.media_url(
+            "series",
+            episode_id,
+            Some(container_extension.unwrap_or("mp4")),
+        )
+    }
+
+    /// URL stored for a channel row of any content type. A series itself is
+    /// not playable, so its row points at the series path without an
+    /// extension; episodes get their own URLs from `episode_url`.
+    pub fn stream_url(&self, channel: &Channel) -> String {
+        match channel.content_type.as_deref() {
+            Some("movie") => {
+                self.movie_url(&channel.stream_id, channel.container_extension.as_deref())
+            }
+            Some("series") => self.media_url("series", &channel.stream_id, None),
+            _ => self.live_stream_url(&channel.stream_id),
+        }
+    }
+
+    /// Builds a `player_api.php` URL with the credentials, the optional
+    /// action and any extra query parameters properly encoded.
+    fn api_url(&self, action: Option<&str>, params: &[(&str, &str)]) -> Result<Url, Error> {
+        let mut url = Url::parse(&format!("{}/player_api.php", self.server_url))?;
+        {
+            let mut query = url.query_pairs_mut();
+            query.append_pair("username", &self.username);
+            query.append_pair("password", &self.password);
+            if let Some(action) = action {
+                query.append_pair("action", action);
+            }
+            for (key, value) in params {
+                query.append_pair(key, value);
+            }
+        }
+        Ok(url)
+    }
+
+    /// Calls `player_api.php` and deserializes the JSON body into `T`.
+    async fn get<T: DeserializeOwned>(
+        &self,
+        action: Option<&str>,
+        params: &[(&str, &str)],
+    ) -> Result<T, Error> {
+        self.get_with_status(action, params).await.1
+    }
+
+    /// Like `get`, but also returns the HTTP status code whenever the server
+    /// answered, so callers can report it even when the body was unusable.
+    async fn get_with_status<T: DeserializeOwned>(
+        &self,
+        action: Option<&str>,
+        params: &[(&str, &str)],
+    ) -> (Option<u16>, Result<T, Error>) {
+        let url = match self.api_url(action, params) {
+            Ok(url) => url,
+            Err(e) => return (None, Err(e)),
+        };
+        println!(
+            "Requesting {} from {}",
+            action.unwrap_or("account info"),
+            self.server_url
+        );
+
+        let (status, body) = self.get_text(url).await;
+        (
+            status,
+            body.and_then(|body| serde_json::from_str(&body).map_err(Error::from)),
+        )
+    }
+
+    /// GETs `url` within the provider's request limit and returns the body,
+    /// along with the HTTP status whenever the server answered.
+    async fn get_text(&self, url: Url) -> (Option<u16>, Result<String, Error>) {
+        let _permit = match self.limit.acquire().await {
+            Ok(permit) => permit,
+            Err(e) => return (None, Err(Error::Internal(e.to_string()))),
+        };
+
+        let response = match self.client.get(url).send().await {
+            Ok(response) => response,
+            Err(e) => return (None, Err(e.into())),
+        };
+        let status = response.status().as_u16();
+
+        let result = match response.error_for_status() {
+            Ok(response) => response.text().await.map_err(Error::from),
+            Err(e) => Err(e.into()),
+        };
+
+        (Some(status), result)
+    }
+
+    /// URL of the server's XMLTV guide.
+    pub fn xmltv_url(&self) -> Result<Url, Error> {
+        let mut url = Url::parse(&format!("{}/xmltv.php", self.server_url))?;
+        url.query_pairs_mut()
+            .append_pair("username", &self.username)
+            .append_pair("password", &self.password);
+        Ok(url)
+    }
+
+    /// Downloads the `get.php?type=m3u_plus` export, which panels without a
+    /// working `player_api.php` still tend to serve.
+    pub async fn get_m3u(&self, output: Option<&str>) -> (Option<u16>, Result<String, Error>) {
+        let mut url = match Url::parse(&format!("{}/get.php", self.server_url)) {
+            Ok(url) => url,
+            Err(e) => return (None, Err(e.into())),
+        };
+        url.query_pairs_mut()
+            .append_pair("username", &self.username)
+            .append_pair("password", &self.password)
+            .append_pair("type", "m3u_plus")
+            .append_pair("output", output.unwrap_or("ts"));
+        println!("Requesting get.php from {}", self.server_url);
+
+        self.get_text(url).await
+    }
+
+    /// Account and server details; the status tells a dead server apart
+    /// from rejected credentials.
+    pub async fn get_account_info_with_status(&self) -> (Option<u16>, Result<AccountInfo, Error>) {
+        self.get_with_status(None, &[]).await
+    }
+
+    pub async fn get_live_categories_with_status(
+        &self,
+    ) -> (Option<u16>, Result<Vec<XtreamCategory>, Error>) {
+        self.get_with_status(Some("get_live_categories"), &[]).await
+    }
+
+    pub async fn get_vod_categories_with_status(
+        &self,
+    ) -> (Option<u16>, Result<Vec<XtreamCategory>, Error>) {
+        self.get_with_status(Some("get_vod_categories"), &[]).await
+    }
+
+    pub async fn get_series_categories_with_status(
+        &self,
+    ) -> (Option<u16>, Result<Vec<XtreamCategory>, Error>) {
+        self.get_with_status(Some("get_series_categories"), &[])
+            .await
+    }
+
+    pub async fn get_live_streams(
+        &self,
+        category_id: Option<&str>,
+    ) -> Result<Vec<LiveStream>, Error> {
+        self.get_live_streams_with_status(category_id).await.1
+    }
+
+    pub async fn get_live_streams_with_status(
+        &self,
+        category_id: Option<&str>,
+    ) -> (Option<u16>, Result<Vec<LiveStream>, Error>) {
+        self.get_with_status(Some("get_live_streams"), &category_param(category_id))
+            .await
+    }
+
+    pub async fn get_vod_streams(
+        &self,
+        category_id: Option<&str>,
+    ) -> Result<Vec<VodStream>, Error> {
+        self.get_vod_streams_with_status(category_id).await.1
+    }
+
+    pub async fn get_vod_streams_with_status(
+        &self,
+        category_id: Option<&str>,
+    ) -> (Option<u16>, Result<Vec<VodStream>, Error>) {
+        self.get_with_status(Some("get_vod_streams"), &category_param(category_id))
+            .await
+    }
+
+    pub async fn get_vod_info(&self, vod_id: &str) -> Result<VodInfo, Error> {
+        self.get(Some("get_vod_info"), &[("vod_id", vod_id)]).await
+    }
+
+    pub async fn get_series(&self, category_id: Option<&str>) -> Result<Vec<Series>, Error> {
+        self.get_series_with_status(category_id).await.1
+    }
+
+    pub async fn get_series_with_status(
+        &self,
+        category_id: Option<&str>,
+    ) -> (Option<u16>, Result<Vec<Series>, Error>) {
+        self.get_with_status(Some("get_series"), &category_param(category_id))
+            .await
+    }
+
+    pub async fn get_series_info(&self, series_id: &str) -> Result<SeriesInfo, Error> {
+        self.get(Some("get_series_info"), &[("series_id", series_id)])
+            .await
+    }
+
+    /// The next `limit` listings of a live stream.
+    pub async fn get_short_epg(&self, stream_id: &str, limit: u32) -> Result<ShortEpg, Error> {
+        let limit = limit.to_string();
+        self.get(
+            Some("get_short_epg"),
+            &[("stream_id", stream_id), ("limit", &limit)],
+        )
+        .await
+    }
+
+    /// Every listing the server holds for a live stream, past ones included.
+    pub async fn get_simple_data_table(&self, stream_id: &str) -> Result<ShortEpg, Error> {
+        self.get(Some("get_simple_data_table"), &[("stream_id", stream_id)])
+            .await
+    }
+}
+
+fn category_param(category_id: Option<&str>) -> Vec<(&str, &str)> {
+    match category_id {
+        Some(id) => vec![("category_id", id)],
+        None => Vec::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn formats(formats: &[&str]) -> Vec<String> {
+        formats.iter().map(|format| format.to_string()).collect()
+    }
+
+    #[test]
+    fn keeps_the_preference_when_allowed_or_unknown() {
+        assert_eq!(live_output_format(Some("m3u8"), &[]), "m3u8");
+        assert_eq!(live_output_format(Some("HLS"), &[]), "m3u8");
+        assert_eq!(
+            live_output_format(Some("m3u8"), &formats(&["ts", "m3u8", "rtmp"])),
+            "m3u8"
+        );
+        assert_eq!(live_output_format(None, &[]), "ts");
+        assert_eq!(live_output_format(Some("flv"), &[]), "ts");
+    }
+
+    #[test]
+    fn falls_back_to_an_allowed_format() {
+        assert_eq!(
+            live_output_format(Some("ts"), &formats(&["m3u8", "rtmp"])),
+            "m3u8"
+        );
+        assert_eq!(live_output_format(None, &formats(&["m3u8"])), "m3u8");
+        assert_eq!(
+            live_output_format(Some("m3u8"), &formats(&["mpegts"])),
+            "ts"
+        );
+    }
+
+    #[test]
+    fn ignores_the_preference_when_no_allowed_format_is_usable() {
+        assert_eq!(live_output_format(Some("m3u8"), &formats(&["rtmp"])), "ts");
+        assert_eq!(live_output_format(None, &formats(&["rtmp"])), "ts");
+    }
+}
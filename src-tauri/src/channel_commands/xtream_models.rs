@@ -0,0 +1,681 @@
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::Value;
+
+use crate::models::Channel;
+
+// Xtream panels are inconsistent about JSON types: ids, counters and ratings
+// arrive as strings on some servers and numbers on others, and empty values
+// are sent as "", null or omitted. These helpers accept all of them.
+
+fn string_or_number<'de, D>(deserializer: D) -> Result<String, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Ok(opt_string(deserializer)?.unwrap_or_default())
+}
+
+fn opt_string<'de, D>(deserializer: D) -> Result<Option<String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Ok(match Option::<Value>::deserialize(deserializer)? {
+        Some(Value::String(s)) if !s.is_empty() => Some(s),
+        Some(Value::Number(n)) => Some(n.to_string()),
+        Some(Value::Bool(b)) => Some(b.to_string()),
+        _ => None,
+    })
+}
+
+fn opt_i64<'de, D>(deserializer: D) -> Result<Option<i64>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Ok(match Option::<Value>::deserialize(deserializer)? {
+        Some(Value::Number(n)) => n.as_i64().or_else(|| n.as_f64().map(|f| f as i64)),
+        Some(Value::String(s)) => s.trim().parse().ok(),
+        Some(Value::Bool(b)) => Some(b as i64),
+        _ => None,
+    })
+}
+
+fn opt_f64<'de, D>(deserializer: D) -> Result<Option<f64>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Ok(match Option::<Value>::deserialize(deserializer)? {
+        Some(Value::Number(n)) => n.as_f64(),
+        Some(Value::String(s)) => s.trim().parse().ok(),
+        _ => None,
+    })
+}
+
+fn string_list<'de, D>(deserializer: D) -> Result<Vec<String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Ok(match Option::<Value>::deserialize(deserializer)? {
+        Some(Value::Array(items)) => items
+            .into_iter()
+            .filter_map(|item| match item {
+                Value::String(s) if !s.is_empty() => Some(s),
+                _ => None,
+            })
+            .collect(),
+        Some(Value::String(s)) if !s.is_empty() => vec![s],
+        _ => Vec::new(),
+    })
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct XtreamCategory {
+    #[serde(default, deserialize_with = "string_or_number")]
+    pub category_id: String,
+    #[serde(default, deserialize_with = "string_or_number")]
+    pub category_name: String,
+    #[serde(default, deserialize_with = "opt_i64")]
+    pub parent_id: Option<i64>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct LiveStream {
+    #[serde(default, deserialize_with = "opt_i64")]
+    pub num: Option<i64>,
+    #[serde(default, deserialize_with = "string_or_number")]
+    pub name: String,
+    #[serde(default, deserialize_with = "opt_string")]
+    pub stream_type: Option<String>,
+    #[serde(default, deserialize_with = "string_or_number")]
+    pub stream_id: String,
+    #[serde(default, deserialize_with = "opt_string")]
+    pub stream_icon: Option<String>,
+    #[serde(default, deserialize_with = "opt_string")]
+    pub epg_channel_id: Option<String>,
+    #[serde(default, deserialize_with = "opt_string")]
+    pub added: Option<String>,
+    #[serde(default, deserialize_with = "opt_string")]
+    pub custom_sid: Option<String>,
+    #[serde(default, deserialize_with = "opt_i64")]
+    pub tv_archive: Option<i64>,
+    #[serde(default, deserialize_with = "opt_string")]
+    pub direct_source: Option<String>,
+    #[serde(default, deserialize_with = "opt_i64")]
+    pub tv_archive_duration: Option<i64>,
+    #[serde(default, deserialize_with = "opt_string")]
+    pub category_id: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct VodStream {
+    #[serde(default, deserialize_with = "opt_i64")]
+    pub num: Option<i64>,
+    #[serde(default, deserialize_with = "string_or_number")]
+    pub name: String,
+    #[serde(default, deserialize_with = "opt_string")]
+    pub stream_type: Option<String>,
+    #[serde(default, deserialize_with = "string_or_number")]
+    pub stream_id: String,
+    #[serde(default, deserialize_with = "opt_string")]
+    pub stream_icon: Option<String>,
+    #[serde(default, deserialize_with = "opt_string")]
+    pub rating: Option<String>,
+    #[serde(default, deserialize_with = "opt_f64")]
+    pub rating_5based: Option<f64>,
+    #[serde(default, deserialize_with = "opt_string")]
+    pub added: Option<String>,
+    #[serde(default, deserialize_with = "opt_string")]
+    pub category_id: Option<String>,
+    #[serde(default, deserialize_with = "opt_string")]
+    pub container_extension: Option<String>,
+    #[serde(default, deserialize_with = "opt_string")]
+    pub custom_sid: Option<String>,
+    #[serde(default, deserialize_with = "opt_string")]
+    pub direct_source: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct Series {
+    #[serde(default, deserialize_with = "opt_i64")]
+    pub num: Option<i64>,
+    #[serde(default, deserialize_with = "string_or_number")]
+    pub name: String,
+    #[serde(default, deserialize_with = "string_or_number")]
+    pub series_id: String,
+    #[serde(default, deserialize_with = "opt_string")]
+    pub cover: Option<String>,
+    #[serde(default, deserialize_with = "opt_string")]
+    pub plot: Option<String>,
+    #[serde(default, deserialize_with = "opt_string")]
+    pub cast: Option<String>,
+    #[serde(default, deserialize_with = "opt_string")]
+    pub director: Option<String>,
+    #[serde(default, deserialize_with = "opt_string")]
+    pub genre: Option<String>,
+    #[serde(default, alias = "releaseDate", deserialize_with = "opt_string")]
+    pub release_date: Option<String>,
+    #[serde(default, deserialize_with = "opt_string")]
+    pub last_modified: Option<String>,
+    #[serde(default, deserialize_with = "opt_string")]
+    pub rating: Option<String>,
+    #[serde(default, deserialize_with = "opt_f64")]
+    pub rating_5based: Option<f64>,
+    #[serde(default, deserialize_with = "string_list")]
+    pub backdrop_path: Vec<String>,
+    #[serde(default, deserialize_with = "opt_string")]
+    pub youtube_trailer: Option<String>,
+    #[serde(default, deserialize_with = "opt_string")]
+    pub episode_run_time: Option<String>,
+    #[serde(default, deserialize_with = "opt_string")]
+    pub category_id: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct SeriesSeason {
+    #[serde(default, deserialize_with = "opt_i64")]
+    pub season_number: Option<i64>,
+    #[serde(default, deserialize_with = "opt_string")]
+    pub name: Option<String>,
+    #[serde(default, deserialize_with = "opt_string")]
+    pub overview: Option<String>,
+    #[serde(default, deserialize_with = "opt_string")]
+    pub air_date: Option<String>,
+    #[serde(default, deserialize_with = "opt_i64")]
+    pub episode_count: Option<i64>,
+    #[serde(default, deserialize_with = "opt_string")]
+    pub cover: Option<String>,
+    #[serde(default, deserialize_with = "opt_string")]
+    pub cover_big: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct EpisodeInfo {
+    #[serde(default, deserialize_with = "opt_i64")]
+    pub duration_secs: Option<i64>,
+    #[serde(default, deserialize_with = "opt_string")]
+    pub duration: Option<String>,
+    #[serde(default, deserialize_with = "opt_string")]
+    pub plot: Option<String>,
+    #[serde(default, deserialize_with = "opt_string")]
+    pub movie_image: Option<String>,
+    #[serde(default, deserialize_with = "opt_f64")]
+    pub rating: Option<f64>,
+    #[serde(default, alias = "releasedate", deserialize_with = "opt_string")]
+    pub release_date: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct SeriesEpisode {
+    #[serde(default, deserialize_with = "string_or_number")]
+    pub id: String,
+    #[serde(default, deserialize_with = "opt_i64")]
+    pub episode_num: Option<i64>,
+    #[serde(default, deserialize_with = "opt_string")]
+    pub title: Option<String>,
+    #[serde(default, deserialize_with = "opt_string")]
+    pub container_extension: Option<String>,
+    #[serde(default, deserialize_with = "opt_i64")]
+    pub season: Option<i64>,
+    #[serde(default, deserialize_with = "opt_string")]
+    pub added: Option<String>,
+    #[serde(default, deserialize_with = "opt_string")]
+    pub direct_source: Option<String>,
+    // Some panels send `[]` instead of an object when there is no info
+    #[serde(default, deserialize_with = "episode_info")]
+    pub info: EpisodeInfo,
+}
+
+fn episode_info<'de, D>(deserializer: D) -> Result<EpisodeInfo, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Ok(match Option::<Value>::deserialize(deserializer)? {
+        Some(value @ Value::Object(_)) => serde_json::from_value(value).unwrap_or_default(),
+        _ => EpisodeInfo::default(),
+    })
+}
+
+/// `seasons` arrives as an array or as an object keyed by season number.
+fn season_list<'de, D>(deserializer: D) -> Result<Vec<SeriesSeason>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let items: Vec<Value> = match Option::<Value>::deserialize(deserializer)? {
+        Some(Value::Array(items)) => items,
+        Some(Value::Object(items)) => items.into_iter().map(|(_, item)| item).collect(),
+        _ => Vec::new(),
+    };
+    Ok(items
+        .into_iter()
+        .filter_map(|item| serde_json::from_value(item).ok())
+        .collect())
+}
+
+/// `episodes` is usually an object of per-season arrays keyed by season
+/// number, but some panels send an array of arrays. Episodes without a
+/// `season` field take it from their key.
+fn episode_list<'de, D>(deserializer: D) -> Result<Vec<SeriesEpisode>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let groups: Vec<(Option<i64>, Value)> = match Option::<Value>::deserialize(deserializer)? {
+        Some(Value::Object(groups)) => groups
+            .into_iter()
+            .map(|(season, episodes)| (season.trim().parse().ok(), episodes))
+            .collect(),
+        Some(Value::Array(groups)) => groups
+            .into_iter()
+            .map(|episodes| (None, episodes))
+            .collect(),
+        _ => Vec::new(),
+    };
+
+    let mut episodes = Vec::new();
+    for (season, group) in groups {
+        let items = match group {
+            Value::Array(items) => items,
+            item @ Value::Object(_) => vec![item],
+            _ => continue,
+        };
+        for item in items {
+            if let Ok(mut episode) = serde_json::from_value::<SeriesEpisode>(item) {
+                if episode.season.is_none() {
+                    episode.season = season;
+                }
+                if !episode.id.is_empty() {
+                    episodes.push(episode);
+                }
+            }
+        }
+    }
+    Ok(episodes)
+}
+
+/// Response of the `get_series_info` action.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct SeriesInfo {
+    #[serde(default, deserialize_with = "season_list")]
+    pub seasons: Vec<SeriesSeason>,
+    #[serde(default, deserialize_with = "episode_list")]
+    pub episodes: Vec<SeriesEpisode>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct VodInfoDetails {
+    #[serde(default, deserialize_with = "opt_string")]
+    pub tmdb_id: Option<String>,
+    #[serde(default, deserialize_with = "opt_string")]
+    pub imdb_id: Option<String>,
+    #[serde(default, deserialize_with = "opt_string")]
+    pub name: Option<String>,
+    #[serde(default, deserialize_with = "opt_string")]
+    pub o_name: Option<String>,
+    #[serde(default, deserialize_with = "opt_string")]
+    pub cover_big: Option<String>,
+    #[serde(default, deserialize_with = "opt_string")]
+    pub movie_image: Option<String>,
+    #[serde(default, alias = "releasedate", deserialize_with = "opt_string")]
+    pub release_date: Option<String>,
+    #[serde(default, deserialize_with = "opt_string")]
+    pub youtube_trailer: Option<String>,
+    #[serde(default, deserialize_with = "opt_string")]
+    pub director: Option<String>,
+    #[serde(default, deserialize_with = "opt_string")]
+    pub actors: Option<String>,
+    #[serde(default, deserialize_with = "opt_string")]
+    pub cast: Option<String>,
+    #[serde(default, deserialize_with = "opt_string")]
+    pub description: Option<String>,
+    #[serde(default, deserialize_with = "opt_string")]
+    pub plot: Option<String>,
+    #[serde(default, deserialize_with = "opt_string")]
+    pub country: Option<String>,
+    #[serde(default, deserialize_with = "opt_string")]
+    pub genre: Option<String>,
+    #[serde(default, deserialize_with = "string_list")]
+    pub backdrop_path: Vec<String>,
+    #[serde(default, deserialize_with = "opt_i64")]
+    pub duration_secs: Option<i64>,
+    #[serde(default, deserialize_with = "opt_string")]
+    pub duration: Option<String>,
+    /// Stream info as reported by the panel's ffprobe, kept verbatim.
+    #[serde(default)]
+    pub video: Option<Value>,
+    #[serde(default)]
+    pub audio: Option<Value>,
+    #[serde(default, deserialize_with = "opt_i64")]
+    pub bitrate: Option<i64>,
+    #[serde(default, deserialize_with = "opt_f64")]
+    pub rating: Option<f64>,
+}
+
+fn vod_info_details<'de, D>(deserializer: D) -> Result<VodInfoDetails, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Ok(match Option::<Value>::deserialize(deserializer)? {
+        Some(value @ Value::Object(_)) => serde_json::from_value(value).unwrap_or_default(),
+        _ => VodInfoDetails::default(),
+    })
+}
+
+/// Response of the `get_vod_info` action. Like episodes, `info` is `[]` on
+/// panels that know nothing about the movie.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct VodInfo {
+    #[serde(default, deserialize_with = "vod_info_details")]
+    pub info: VodInfoDetails,
+}
+
+/// A listing of `get_short_epg` or `get_simple_data_table`. `title` and
+/// `description` are base64-encoded; `start` and `end` are in the server's
+/// timezone, unlike the Unix timestamps.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct ShortEpgListing {
+    #[serde(default, deserialize_with = "opt_string")]
+    pub id: Option<String>,
+    #[serde(default, deserialize_with = "opt_string")]
+    pub epg_id: Option<String>,
+    #[serde(default, deserialize_with = "opt_string")]
+    pub title: Option<String>,
+    #[serde(default, deserialize_with = "opt_string")]
+    pub lang: Option<String>,
+    #[serde(default, deserialize_with = "opt_string")]
+    pub start: Option<String>,
+    #[serde(default, alias = "stop", deserialize_with = "opt_string")]
+    pub end: Option<String>,
+    #[serde(default, deserialize_with = "opt_string")]
+    pub description: Option<String>,
+    #[serde(default, deserialize_with = "opt_string")]
+    pub channel_id: Option<String>,
+    #[serde(default, deserialize_with = "opt_i64")]
+    pub start_timestamp: Option<i64>,
+    #[serde(default, deserialize_with = "opt_i64")]
+    pub stop_timestamp: Option<i64>,
+    #[serde(default, deserialize_with = "opt_i64")]
+    pub now_playing: Option<i64>,
+    #[serde(default, deserialize_with = "opt_i64")]
+    pub has_archive: Option<i64>,
+}
+
+/// `epg_listings` is an array, but some panels key it by listing id.
+fn listing_list<'de, D>(deserializer: D) -> Result<Vec<ShortEpgListing>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let items: Vec<Value> = match Option::<Value>::deserialize(deserializer)? {
+        Some(Value::Array(items)) => items,
+        Some(Value::Object(items)) => items.into_iter().map(|(_, item)| item).collect(),
+        _ => Vec::new(),
+    };
+    Ok(items
+        .into_iter()
+        .filter_map(|item| serde_json::from_value(item).ok())
+        .collect())
+}
+
+/// Response of the `get_short_epg` and `get_simple_data_table` actions.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct ShortEpg {
+    #[serde(default, deserialize_with = "listing_list")]
+    pub epg_listings: Vec<ShortEpgListing>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct UserInfo {
+    #[serde(default, deserialize_with = "opt_string")]
+    pub username: Option<String>,
+    #[serde(default, deserialize_with = "opt_string")]
+    pub message: Option<String>,
+    #[serde(default, deserialize_with = "opt_i64")]
+    pub auth: Option<i64>,
+    #[serde(default, deserialize_with = "opt_string")]
+    pub status: Option<String>,
+    #[serde(default, deserialize_with = "opt_i64")]
+    pub exp_date: Option<i64>,
+    #[serde(default, deserialize_with = "opt_string")]
+    pub is_trial: Option<String>,
+    #[serde(default, deserialize_with = "opt_i64")]
+    pub active_cons: Option<i64>,
+    #[serde(default, deserialize_with = "opt_i64")]
+    pub created_at: Option<i64>,
+    #[serde(default, deserialize_with = "opt_i64")]
+    pub max_connections: Option<i64>,
+    #[serde(default, deserialize_with = "string_list")]
+    pub allowed_output_formats: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct ServerInfo {
+    #[serde(default, deserialize_with = "opt_string")]
+    pub url: Option<String>,
+    #[serde(default, deserialize_with = "opt_string")]
+    pub port: Option<String>,
+    #[serde(default, deserialize_with = "opt_string")]
+    pub https_port: Option<String>,
+    #[serde(default, deserialize_with = "opt_string")]
+    pub server_protocol: Option<String>,
+    #[serde(default, deserialize_with = "opt_string")]
+    pub rtmp_port: Option<String>,
+    #[serde(default, deserialize_with = "opt_string")]
+    pub timezone: Option<String>,
+    #[serde(default, deserialize_with = "opt_i64")]
+    pub timestamp_now: Option<i64>,
+    #[serde(default, deserialize_with = "opt_string")]
+    pub time_now: Option<String>,
+}
+
+/// Response of `player_api.php` called without an action.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct AccountInfo {
+    #[serde(default)]
+    pub user_info: UserInfo,
+    #[serde(default)]
+    pub server_info: Option<ServerInfo>,
+}
+
+impl LiveStream {
+    pub fn into_channel(self, playlist_id: i64) -> Channel {
+        Channel {
+            playlist_id,
+            stream_id: self.stream_id,
+            name: self.name,
+            stream_type: self.stream_type.unwrap_or_else(|| "live".to_string()),
+            category_id: self.category_id,
+            stream_icon: self.stream_icon,
+            epg_channel_id: self.epg_channel_id,
+            added: self.added,
+            custom_sid: self.custom_sid,
+            tv_archive: self.tv_archive,
+            direct_source: self.direct_source,
+            tv_archive_duration: self.tv_archive_duration,
+            num: self.num.map(|n| n.to_string()),
+            content_type: Some("live".to_string()),
+            ..Default::default()
+        }
+    }
+}
+
+impl VodStream {
+    pub fn into_channel(self, playlist_id: i64) -> Channel {
+        Channel {
+            playlist_id,
+            stream_id: self.stream_id,
+            name: self.name,
+            stream_type: self.stream_type.unwrap_or_else(|| "vod".to_string()),
+            category_id: self.category_id,
+            stream_icon: self.stream_icon,
+            rating: self.rating,
+            rating_5based: self.rating_5based,
+            added: self.added,
+            container_extension: self.container_extension,
+            custom_sid: self.custom_sid,
+            direct_source: self.direct_source,
+            num: self.num.map(|n| n.to_string()),
+            content_type: Some("movie".to_string()),
+            ..Default::default()
+        }
+    }
+}
+
+impl Series {
+    pub fn into_channel(self, playlist_id: i64) -> Channel {
+        Channel {
+            playlist_id,
+            stream_id: self.series_id,
+            name: self.name,
+            stream_type: "series".to_string(),
+            category_id: self.category_id,
+            cover: self.cover,
+            plot: self.plot,
+            cast: self.cast,
+            director: self.director,
+            genre: self.genre,
+            release_date: self.release_date,
+            rating: self.rating,
+            rating_5based: self.rating_5based,
+            backdrop_path: Some(self.backdrop_path),
+            youtube_trailer: self.youtube_trailer,
+            episode_run_time: self.episode_run_time,
+            num: self.num.map(|n| n.to_string()),
+            content_type: Some("series".to_string()),
+            ..Default::default()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn reads_numbers_sent_as_strings_and_empty_strings_as_missing() {
+        let streams: Vec<VodStream> = serde_json::from_value(json!([
+            {
+                "num": "7", "name": 1917, "stream_id": "42", "rating": 7.5,
+                "rating_5based": "3.8", "category_id": 12, "container_extension": "",
+                "added": null
+            },
+            { "num": 8.0, "name": "", "stream_id": 43, "rating_5based": "", "custom_sid": false }
+        ]))
+        .unwrap();
+
+        assert_eq!(streams[0].num, Some(7));
+        assert_eq!(streams[0].name, "1917");
+        assert_eq!(streams[0].stream_id, "42");
+        assert_eq!(streams[0].rating.as_deref(), Some("7.5"));
+        assert_eq!(streams[0].rating_5based, Some(3.8));
+        assert_eq!(streams[0].category_id.as_deref(), Some("12"));
+        assert_eq!(streams[0].container_extension, None);
+        assert_eq!(streams[0].added, None);
+
+        assert_eq!(streams[1].num, Some(8));
+        assert_eq!(streams[1].name, "");
+        assert_eq!(streams[1].stream_id, "43");
+        assert_eq!(streams[1].rating_5based, None);
+        assert_eq!(streams[1].custom_sid.as_deref(), Some("false"));
+    }
+
+    #[test]
+    fn reads_allowed_output_formats_as_a_list_or_a_string() {
+        let account: AccountInfo = serde_json::from_value(json!({
+            "user_info": {
+                "auth": 1, "status": "Active", "exp_date": "1735689600", "is_trial": "0",
+                "active_cons": "0", "max_connections": "2",
+                "allowed_output_formats": ["m3u8", "ts", "", "rtmp"]
+            },
+            "server_info": { "timezone": "Europe/London", "timestamp_now": 1706733000 }
+        }))
+        .unwrap();
+        let user = account.user_info;
+        assert_eq!(user.auth, Some(1));
+        assert_eq!(user.exp_date, Some(1735689600));
+        assert_eq!(user.max_connections, Some(2));
+        assert_eq!(user.allowed_output_formats, ["m3u8", "ts", "rtmp"]);
+        let server = account.server_info.unwrap();
+        assert_eq!(server.timezone.as_deref(), Some("Europe/London"));
+        assert_eq!(server.timestamp_now, Some(1706733000));
+
+        let user: UserInfo = serde_json::from_value(json!({
+            "auth": "1", "exp_date": null, "allowed_output_formats": "ts"
+        }))
+        .unwrap();
+        assert_eq!(user.auth, Some(1));
+        assert_eq!(user.exp_date, None);
+        assert_eq!(user.allowed_output_formats, ["ts"]);
+
+        let user: UserInfo =
+            serde_json::from_value(json!({ "allowed_output_formats": "" })).unwrap();
+        assert!(user.allowed_output_formats.is_empty());
+    }
+
+    #[test]
+    fn reads_seasons_and_episodes_keyed_by_season() {
+        let info: SeriesInfo = serde_json::from_value(json!({
+            "seasons": {
+                "1": { "season_number": "1", "name": "Season 1", "episode_count": "2" },
+                "2": { "season_number": 2, "air_date": "" }
+            },
+            "episodes": {
+                "1": [
+                    { "id": "1001", "episode_num": "1", "title": "Pilot",
+                      "container_extension": "mkv", "info": { "duration_secs": "3120", "rating": "8.1" } },
+                    { "id": 1002, "episode_num": 2, "season": "1", "info": [] }
+                ],
+                "2": { "id": "2001", "episode_num": 1 },
+                "x": [{ "id": "", "episode_num": 3 }]
+            }
+        }))
+        .unwrap();
+
+        let mut seasons: Vec<(Option<i64>, Option<String>)> = info
+            .seasons
+            .iter()
+            .map(|season| (season.season_number, season.name.clone()))
+            .collect();
+        seasons.sort();
+        assert_eq!(
+            seasons,
+            [(Some(1), Some("Season 1".to_string())), (Some(2), None)]
+        );
+
+        let mut episodes: Vec<(&str, Option<i64>, Option<i64>)> = info
+            .episodes
+            .iter()
+            .map(|episode| (episode.id.as_str(), episode.season, episode.episode_num))
+            .collect();
+        episodes.sort();
+        assert_eq!(
+            episodes,
+            [
+                ("1001", Some(1), Some(1)),
+                ("1002", Some(1), Some(2)),
+                ("2001", Some(2), Some(1))
+            ]
+        );
+        let pilot = info.episodes.iter().find(|e| e.id == "1001").unwrap();
+        assert_eq!(pilot.info.duration_secs, Some(3120));
+        assert_eq!(pilot.info.rating, Some(8.1));
+        let second = info.episodes.iter().find(|e| e.id == "1002").unwrap();
+        assert_eq!(second.info.duration_secs, None);
+    }
+
+    #[test]
+    fn reads_seasons_and_episodes_sent_as_arrays() {
+        let info: SeriesInfo = serde_json::from_value(json!({
+            "seasons": [{ "season_number": 1 }],
+            "episodes": [[{ "id": "1001", "season": 1 }], [{ "id": "2001" }]]
+        }))
+        .unwrap();
+        assert_eq!(info.seasons.len(), 1);
+        let episodes: Vec<(&str, Option<i64>)> = info
+            .episodes
+            .iter()
+            .map(|episode| (episode.id.as_str(), episode.season))
+            .collect();
+        assert_eq!(episodes, [("1001", Some(1)), ("2001", None)]);
+
+        let empty: SeriesInfo =
+            serde_json::from_value(json!({ "seasons": [], "episodes": null })).unwrap();
+        assert!(empty.seasons.is_empty() && empty.episodes.is_empty());
+    }
+}
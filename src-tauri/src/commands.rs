@@ -43,21 +43,24 @@ pub async fn remove_from_favorites(
     db: State<'_, DbConnection>,
     playlist_id: i64,
     stream_id: String,
+    content_type: String,
 ) -> Result<(), Error> {
     let conn = db.0.lock().unwrap();
     conn.execute(
-        "DELETE FROM favorites WHERE playlist_id = ?1 AND stream_id = ?2",
-        params![playlist_id, stream_id],
+        "DELETE FROM favorites WHERE playlist_id = ?1 AND content_type = ?2 AND stream_id = ?3",
+        params![playlist_id, content_type, stream_id],
     )?;
     Ok(())
 }
 
 #[tauri::command]
-pub async fn get_favorites(db: State<'_, DbConnection>) -> Result<Vec<(i64, String)>, Error> {
+pub async fn get_favorites(
+    db: State<'_, DbConnection>,
+) -> Result<Vec<(i64, String, String)>, Error> {
     let conn = db.0.lock().unwrap();
-    let mut stmt = conn.prepare("SELECT playlist_id, stream_id FROM favorites")?;
+    let mut stmt = conn.prepare("SELECT playlist_id, stream_id, content_type FROM favorites")?;
     let favorites = stmt
-        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?
         .collect::<Result<Vec<_>, _>>()?;
     Ok(favorites)
 }
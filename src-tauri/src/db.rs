@@ -68,17 +68,272 @@ pub fn migrate_db_v1(conn: &Connection) -> SqliteResult<()> {
     Ok(())
 }
 
+fn add_column_if_missing(
+    conn: &Connection,
+    table: &str,
+    column: &str,
+    definition: &str,
+) -> SqliteResult<()> {
+    let columns: Vec<String> = conn
+        .prepare(&format!("PRAGMA table_info({})", table))?
+        .query_map([], |row| row.get::<_, String>(1))?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    if !columns.iter().any(|c| c == column) {
+        println!("Adding {} column to {} table", column, table);
+        conn.execute(
+            &format!(
+                "ALTER TABLE {} ADD COLUMN \"{}\" {}",
+                table, column, definition
+            ),
+            [],
+        )?;
+    }
+    Ok(())
+}
+
+pub fn migrate_db_v2(conn: &Connection) -> SqliteResult<()> {
+    // Background refresh settings and outcome, see channel_commands::auto_refresh
+    add_column_if_missing(
+        conn,
+        "playlists",
+        "refresh_interval_minutes",
+        "INTEGER NOT NULL DEFAULT 1440",
+    )?;
+    add_column_if_missing(conn, "playlists", "last_error", "TEXT")?;
+    Ok(())
+}
+
+pub fn migrate_db_v3(conn: &Connection) -> SqliteResult<()> {
+    // Preferred stream container taken from pasted provider links
+    add_column_if_missing(conn, "playlists", "output_format", "TEXT")?;
+    Ok(())
+}
+
+pub fn migrate_db_v4(conn: &Connection) -> SqliteResult<()> {
+    // Metadata from Xtream live streams and extended M3U attributes
+    for (column, definition) in [
+        ("stream_icon", "TEXT"),
+        ("epg_channel_id", "TEXT"),
+        ("num", "TEXT"),
+        ("tv_archive", "INTEGER"),
+        ("tv_archive_duration", "INTEGER"),
+        ("tvg_name", "TEXT"),
+        ("tvg_shift", "REAL"),
+        ("tvg_language", "TEXT"),
+        ("tvg_country", "TEXT"),
+        ("catchup", "TEXT"),
+        ("catchup_source", "TEXT"),
+        ("http_user_agent", "TEXT"),
+        ("http_referrer", "TEXT"),
+        ("kodi_props", "TEXT"),
+    ] {
+        add_column_if_missing(conn, "channels", column, definition)?;
+    }
+    Ok(())
+}
+
+pub fn migrate_db_v5(conn: &Connection) -> SqliteResult<()> {
+    // M3U playlists from URLs and local files, see models::SourceType
+    add_column_if_missing(
+        conn,
+        "playlists",
+        "source_type",
+        "TEXT NOT NULL DEFAULT 'xtream'",
+    )?;
+    Ok(())
+}
+
+pub fn migrate_db_v6(conn: &Connection) -> SqliteResult<()> {
+    // Categories used to be global with a UNIQUE category_id, so a second
+    // playlist (or a VOD category sharing a live category's ID) could not be
+    // stored. Rebuild the table scoped per playlist, giving each old row to
+    // the playlists whose channels are in it.
+    let columns: Vec<String> = conn
+        .prepare("PRAGMA table_info(categories)")?
+        .query_map([], |row| row.get::<_, String>(1))?
+        .collect::<Result<Vec<_>, _>>()?;
+    if columns.iter().any(|c| c == "playlist_id") {
+        return Ok(());
+    }
+
+    println!("Rebuilding categories table with a playlist_id column");
+    conn.execute("ALTER TABLE categories RENAME TO categories_v5", [])?;
+    migrations::create_categories_table(conn)?;
+    let copied = conn.execute(
+        "INSERT OR IGNORE INTO categories
+             (playlist_id, category_id, name, content_type, parent_id, created_at, updated_at)
+         SELECT DISTINCT ch.playlist_id, CAST(old.category_id AS TEXT), old.name,
+                old.content_type, old.parent_id, old.created_at, old.updated_at
+         FROM categories_v5 old
+         JOIN channels ch ON ch.category_id = CAST(old.category_id AS TEXT)",
+        [],
+    )?;
+    conn.execute("DROP TABLE categories_v5", [])?;
+    println!("Copied {} categories into the rebuilt table", copied);
+    Ok(())
+}
+
 pub fn init_db(conn: &Connection) -> SqliteResult<()> {
     migrations::create_playlists_table(conn)?;
     migrations::create_categories_table(conn)?;
     migrations::create_streams_table(conn)?;
     migrations::create_epg_data_table(conn)?;
+    migrations::create_epg_channels_table(conn)?;
+    migrations::create_epg_sources_table(conn)?;
+    migrations::create_epg_channel_map_table(conn)?;
     migrations::create_vod_metadata_table(conn)?;
     migrations::create_selected_channel_table(conn)?;
     migrations::create_channels_table(conn)?;
     migrations::create_favorites_table(conn)?;
+    migrations::create_account_status_table(conn)?;
+    migrations::create_series_info_table(conn)?;
+    migrations::create_seasons_table(conn)?;
+    migrations::create_episodes_table(conn)?;
 
     println!("Database schema initialized successfully");
 
     Ok(())
 }
+
+pub fn migrate_db_v7(conn: &Connection) -> SqliteResult<()> {
+    // The remaining Xtream stream, VOD and series fields of models::Channel
+    for (column, definition) in [
+        ("type_name", "TEXT"),
+        ("content_type", "TEXT"),
+        ("added", "TEXT"),
+        ("series_no", "TEXT"),
+        ("live", "TEXT"),
+        ("container_extension", "TEXT"),
+        ("custom_sid", "TEXT"),
+        ("direct_source", "TEXT"),
+        ("plot", "TEXT"),
+        ("cast", "TEXT"),
+        ("director", "TEXT"),
+        ("genre", "TEXT"),
+        ("release_date", "TEXT"),
+        ("rating", "TEXT"),
+        ("rating_5based", "REAL"),
+        ("backdrop_path", "TEXT"),
+        ("youtube_trailer", "TEXT"),
+        ("episode_run_time", "TEXT"),
+        ("cover", "TEXT"),
+    ] {
+        add_column_if_missing(conn, "channels", column, definition)?;
+    }
+    // Rows written before content_type was stored; stream_type tells them apart
+    conn.execute(
+        "UPDATE channels SET content_type = CASE stream_type
+             WHEN 'series' THEN 'series'
+             WHEN 'movie' THEN 'movie'
+             WHEN 'vod' THEN 'movie'
+             ELSE 'live'
+         END
+         WHERE content_type IS NULL",
+        [],
+    )?;
+    Ok(())
+}
+
+pub fn migrate_db_v8(conn: &Connection) -> SqliteResult<()> {
+    // vod_metadata referenced streams(id) and was never written; rebuild it
+    // keyed by playlist and stream ID like channels
+    let columns: Vec<String> = conn
+        .prepare("PRAGMA table_info(vod_metadata)")?
+        .query_map([], |row| row.get::<_, String>(1))?
+        .collect::<Result<Vec<_>, _>>()?;
+    if columns.iter().any(|c| c == "playlist_id") {
+        return Ok(());
+    }
+
+    println!("Rebuilding vod_metadata table with a playlist_id column");
+    conn.execute("DROP TABLE IF EXISTS vod_metadata", [])?;
+    migrations::create_vod_metadata_table(conn)
+}
+
+pub fn migrate_db_v9(conn: &Connection) -> SqliteResult<()> {
+    // epg_data was never written and keyed its channels to streams(epg_id);
+    // rebuild it per playlist
+    let columns: Vec<String> = conn
+        .prepare("PRAGMA table_info(epg_data)")?
+        .query_map([], |row| row.get::<_, String>(1))?
+        .collect::<Result<Vec<_>, _>>()?;
+    if columns.iter().any(|c| c == "playlist_id") {
+        return Ok(());
+    }
+
+    println!("Rebuilding epg_data table with a playlist_id column");
+    conn.execute("DROP TABLE IF EXISTS epg_data", [])?;
+    migrations::create_epg_data_table(conn)
+}
+
+pub fn migrate_db_v10(conn: &Connection) -> SqliteResult<()> {
+    // The guide each EPG channel was taken from when several are merged
+    add_column_if_missing(conn, "epg_channels", "source", "TEXT")
+}
+
+pub fn migrate_db_v11(conn: &Connection) -> SqliteResult<()> {
+    // The EPG grid lists a playlist's live channels by category. Programmes
+    // are looked up through the UNIQUE(playlist_id, channel_id, start) index
+    // of epg_data.
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_channels_playlist_category ON channels (playlist_id, category_id)",
+        [],
+    )?;
+    Ok(())
+}
+
+pub fn migrate_db_v12(conn: &Connection) -> SqliteResult<()> {
+    // Series IDs are numbered apart from live and VOD stream IDs, so a series
+    // could take over the row of the channel sharing its ID. Key channels and
+    // favorites by content type as well.
+    conn.execute(
+        "UPDATE channels SET content_type = 'live' WHERE content_type IS NULL",
+        [],
+    )?;
+    conn.execute("DROP INDEX IF EXISTS idx_channels_playlist_stream", [])?;
+    conn.execute(
+        "CREATE UNIQUE INDEX IF NOT EXISTS idx_channels_playlist_content_stream ON channels (playlist_id, content_type, stream_id)",
+        [],
+    )?;
+
+    let favorites_sql: String = conn.query_row(
+        "SELECT sql FROM sqlite_master WHERE type = 'table' AND name = 'favorites'",
+        [],
+        |row| row.get(0),
+    )?;
+    if favorites_sql.contains("UNIQUE(playlist_id, content_type, stream_id)") {
+        return Ok(());
+    }
+
+    // Favorites were saved with the channel's stream_type as content type.
+    // Stream IDs are still unique per playlist at this point.
+    println!("Rebuilding favorites table keyed by content type");
+    conn.execute("ALTER TABLE favorites RENAME TO favorites_v11", [])?;
+    migrations::create_favorites_table(conn)?;
+    conn.execute(
+        "INSERT OR IGNORE INTO favorites (id, playlist_id, stream_id, content_type, created_at)
+         SELECT f.id, f.playlist_id, f.stream_id,
+                COALESCE(
+                    (SELECT c.content_type FROM channels c
+                     WHERE c.playlist_id = f.playlist_id AND c.stream_id = f.stream_id
+                     LIMIT 1),
+                    CASE f.content_type
+                        WHEN 'series' THEN 'series'
+                        WHEN 'movie' THEN 'movie'
+                        WHEN 'vod' THEN 'movie'
+                        ELSE 'live'
+                    END),
+                f.created_at
+         FROM favorites_v11 f",
+        [],
+    )?;
+    conn.execute("DROP TABLE favorites_v11", [])?;
+    Ok(())
+}
+
+pub fn migrate_db_v13(conn: &Connection) -> SqliteResult<()> {
+    // Which importer wrote a programme, `xmltv` or `short_epg`, so each one
+    // only prunes its own rows
+    add_column_if_missing(conn, "epg_data", "origin", "TEXT NOT NULL DEFAULT 'xmltv'")
+}
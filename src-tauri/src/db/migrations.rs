@@ -22,13 +22,15 @@ pub fn create_playlists_table(conn: &Connection) -> SqliteResult<()> {
 pub fn create_categories_table(conn: &Connection) -> SqliteResult<()> {
     let create_categories_table = "CREATE TABLE IF NOT EXISTS categories (
         id INTEGER PRIMARY KEY,
-        category_id INTEGER NOT NULL UNIQUE,
+        playlist_id INTEGER NOT NULL,
+        category_id TEXT NOT NULL,
         name TEXT NOT NULL,
         content_type TEXT NOT NULL DEFAULT 'live',
-        type TEXT CHECK(type IN ('live', 'vod', 'movie', 'series')) NOT NULL DEFAULT 'movie',
         parent_id INTEGER,
         created_at INTEGER NOT NULL DEFAULT (strftime('%s', 'now')),
-        updated_at INTEGER NOT NULL DEFAULT (strftime('%s', 'now'))
+        updated_at INTEGER NOT NULL DEFAULT (strftime('%s', 'now')),
+        UNIQUE(playlist_id, content_type, category_id),
+        FOREIGN KEY(playlist_id) REFERENCES playlists(id) ON DELETE CASCADE
     )";
 
     conn.execute(create_categories_table, [])?;
@@ -37,6 +39,8 @@ pub fn create_categories_table(conn: &Connection) -> SqliteResult<()> {
 }
 
 pub fn create_streams_table(conn: &Connection) -> SqliteResult<()> {
+    // Legacy: imports write channels, keyed per playlist and content type,
+    // and nothing reads or writes this table any more
     let create_streams_table = "CREATE TABLE IF NOT EXISTS streams (
         id INTEGER PRIMARY KEY,
         stream_id INTEGER NOT NULL UNIQUE,
@@ -56,16 +60,26 @@ pub fn create_streams_table(conn: &Connection) -> SqliteResult<()> {
 }
 
 pub fn create_epg_data_table(conn: &Connection) -> SqliteResult<()> {
+    // Programmes from XMLTV guides, see channel_commands::epg_commands.
+    // import_id marks the import that last wrote a row so rows the guide
+    // dropped can be removed afterwards.
     let create_epg_data_table = "CREATE TABLE IF NOT EXISTS epg_data (
         id INTEGER PRIMARY KEY,
+        playlist_id INTEGER NOT NULL,
         channel_id TEXT NOT NULL,
         start INTEGER NOT NULL,
         end INTEGER NOT NULL,
         title TEXT NOT NULL,
+        sub_title TEXT,
         description TEXT,
+        category TEXT,
+        icon TEXT,
+        episode_num TEXT,
         season INTEGER,
         episode INTEGER,
-        FOREIGN KEY(channel_id) REFERENCES streams(epg_id)
+        import_id INTEGER NOT NULL DEFAULT 0,
+        UNIQUE(playlist_id, channel_id, start),
+        FOREIGN KEY(playlist_id) REFERENCES playlists(id) ON DELETE CASCADE
     )";
 
     conn.execute(create_epg_data_table, [])?;
@@ -73,17 +87,90 @@ pub fn create_epg_data_table(conn: &Connection) -> SqliteResult<()> {
     Ok(())
 }
 
+pub fn create_epg_channels_table(conn: &Connection) -> SqliteResult<()> {
+    let create_epg_channels_table = "CREATE TABLE IF NOT EXISTS epg_channels (
+        playlist_id INTEGER NOT NULL,
+        channel_id TEXT NOT NULL,
+        display_name TEXT,
+        display_names TEXT,
+        icon TEXT,
+        import_id INTEGER NOT NULL DEFAULT 0,
+        PRIMARY KEY(playlist_id, channel_id),
+        FOREIGN KEY(playlist_id) REFERENCES playlists(id) ON DELETE CASCADE
+    )";
+
+    conn.execute(create_epg_channels_table, [])?;
+    println!("Epg channels table created successfully");
+    Ok(())
+}
+
+pub fn create_epg_sources_table(conn: &Connection) -> SqliteResult<()> {
+    // Extra guides of a playlist, see channel_commands::epg_commands
+    let create_epg_sources_table = "CREATE TABLE IF NOT EXISTS epg_sources (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        playlist_id INTEGER NOT NULL,
+        url TEXT NOT NULL,
+        priority INTEGER NOT NULL DEFAULT 0,
+        enabled BOOLEAN NOT NULL DEFAULT 1,
+        created_at TEXT NOT NULL,
+        UNIQUE(playlist_id, url),
+        FOREIGN KEY(playlist_id) REFERENCES playlists(id) ON DELETE CASCADE
+    )";
+
+    conn.execute(create_epg_sources_table, [])?;
+    println!("Epg sources table created successfully");
+    Ok(())
+}
+
+pub fn create_epg_channel_map_table(conn: &Connection) -> SqliteResult<()> {
+    // Guide channels of channels matched by name or set by hand, see
+    // channel_commands::epg_mapping. Keyed by stream_id, not channels.id,
+    // so links survive refreshes that recreate channel rows.
+    let create_epg_channel_map_table = "CREATE TABLE IF NOT EXISTS epg_channel_map (
+        playlist_id INTEGER NOT NULL,
+        stream_id TEXT NOT NULL,
+        epg_channel_id TEXT NOT NULL,
+        manual BOOLEAN NOT NULL DEFAULT 0,
+        score REAL,
+        updated_at TEXT NOT NULL,
+        PRIMARY KEY(playlist_id, stream_id),
+        FOREIGN KEY(playlist_id) REFERENCES playlists(id) ON DELETE CASCADE
+    )";
+
+    conn.execute(create_epg_channel_map_table, [])?;
+    println!("Epg channel map table created successfully");
+    Ok(())
+}
+
 pub fn create_vod_metadata_table(conn: &Connection) -> SqliteResult<()> {
+    // Details from get_vod_info, see channel_commands::vod_commands
     let create_vod_metadata_table = "CREATE TABLE IF NOT EXISTS vod_metadata (
         id INTEGER PRIMARY KEY,
-        stream_id INTEGER REFERENCES streams(id),
+        playlist_id INTEGER NOT NULL,
+        stream_id TEXT NOT NULL,
         rating REAL,
         director TEXT,
         year INTEGER,
         plot TEXT,
         imdb_id TEXT,
+        tmdb_id TEXT,
+        original_name TEXT,
+        duration_secs INTEGER,
+        duration TEXT,
+        genre TEXT,
+        \"cast\" TEXT,
+        country TEXT,
+        release_date TEXT,
+        cover TEXT,
+        backdrop_path TEXT,
+        youtube_trailer TEXT,
+        video TEXT,
+        audio TEXT,
+        bitrate INTEGER,
         created_at INTEGER NOT NULL DEFAULT (strftime('%s', 'now')),
-        updated_at INTEGER NOT NULL DEFAULT (strftime('%s', 'now'))
+        updated_at INTEGER NOT NULL DEFAULT (strftime('%s', 'now')),
+        UNIQUE(playlist_id, stream_id),
+        FOREIGN KEY(playlist_id) REFERENCES playlists(id) ON DELETE CASCADE
     )";
 
     conn.execute(create_vod_metadata_table, [])?;
@@ -164,7 +251,7 @@ pub fn create_favorites_table(conn: &Connection) -> SqliteResult<()> {
         stream_id TEXT NOT NULL,
         content_type TEXT NOT NULL,
         created_at INTEGER NOT NULL DEFAULT (strftime('%s', 'now')),
-        UNIQUE(playlist_id, stream_id),
+        UNIQUE(playlist_id, content_type, stream_id),
         FOREIGN KEY(playlist_id) REFERENCES playlists(id) ON DELETE CASCADE
     )";
 
@@ -172,3 +259,91 @@ pub fn create_favorites_table(conn: &Connection) -> SqliteResult<()> {
     println!("Favorites table created successfully");
     Ok(())
 }
+
+pub fn create_account_status_table(conn: &Connection) -> SqliteResult<()> {
+    let create_account_status_table = "CREATE TABLE IF NOT EXISTS account_status (
+        playlist_id INTEGER PRIMARY KEY,
+        reachable INTEGER NOT NULL,
+        authenticated INTEGER NOT NULL,
+        http_status INTEGER,
+        status TEXT,
+        message TEXT,
+        exp_date INTEGER,
+        is_trial INTEGER NOT NULL DEFAULT 0,
+        active_cons INTEGER,
+        max_connections INTEGER,
+        allowed_output_formats TEXT,
+        server_timezone TEXT,
+        server_https_port TEXT,
+        server_timestamp INTEGER,
+        error TEXT,
+        checked_at TEXT NOT NULL,
+        FOREIGN KEY(playlist_id) REFERENCES playlists(id) ON DELETE CASCADE
+    )";
+
+    conn.execute(create_account_status_table, [])?;
+    println!("Account status table created successfully");
+    Ok(())
+}
+
+pub fn create_series_info_table(conn: &Connection) -> SqliteResult<()> {
+    // One row per series whose seasons and episodes are cached
+    let create_series_info_table = "CREATE TABLE IF NOT EXISTS series_info (
+        playlist_id INTEGER NOT NULL,
+        series_id TEXT NOT NULL,
+        fetched_at TEXT NOT NULL,
+        PRIMARY KEY(playlist_id, series_id),
+        FOREIGN KEY(playlist_id) REFERENCES playlists(id) ON DELETE CASCADE
+    )";
+
+    conn.execute(create_series_info_table, [])?;
+    println!("Series info table created successfully");
+    Ok(())
+}
+
+pub fn create_seasons_table(conn: &Connection) -> SqliteResult<()> {
+    let create_seasons_table = "CREATE TABLE IF NOT EXISTS seasons (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        playlist_id INTEGER NOT NULL,
+        series_id TEXT NOT NULL,
+        season_number INTEGER NOT NULL,
+        name TEXT,
+        overview TEXT,
+        air_date TEXT,
+        episode_count INTEGER,
+        cover TEXT,
+        UNIQUE(playlist_id, series_id, season_number),
+        FOREIGN KEY(playlist_id) REFERENCES playlists(id) ON DELETE CASCADE
+    )";
+
+    conn.execute(create_seasons_table, [])?;
+    println!("Seasons table created successfully");
+    Ok(())
+}
+
+pub fn create_episodes_table(conn: &Connection) -> SqliteResult<()> {
+    let create_episodes_table = "CREATE TABLE IF NOT EXISTS episodes (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        playlist_id INTEGER NOT NULL,
+        series_id TEXT NOT NULL,
+        episode_id TEXT NOT NULL,
+        season_number INTEGER,
+        episode_num INTEGER,
+        title TEXT,
+        duration_secs INTEGER,
+        duration TEXT,
+        plot TEXT,
+        cover TEXT,
+        rating REAL,
+        release_date TEXT,
+        container_extension TEXT,
+        stream_url TEXT NOT NULL,
+        added TEXT,
+        UNIQUE(playlist_id, series_id, episode_id),
+        FOREIGN KEY(playlist_id) REFERENCES playlists(id) ON DELETE CASCADE
+    )";
+
+    conn.execute(create_episodes_table, [])?;
+    println!("Episodes table created successfully");
+    Ok(())
+}
@@ -1,5 +1,6 @@
 use tauri::Manager;
 
+pub mod account_commands;
 pub mod channel_commands;
 pub mod commands;
 pub mod db;
@@ -50,10 +51,26 @@ pub fn run() {
             db::check_and_create_channels_table(&conn)
                 .expect("Failed to check/create channels table");
             db::migrate_db_v1(&conn).expect("Failed to migrate database");
+            db::migrate_db_v2(&conn).expect("Failed to migrate database");
+            db::migrate_db_v3(&conn).expect("Failed to migrate database");
+            db::migrate_db_v4(&conn).expect("Failed to migrate database");
+            db::migrate_db_v5(&conn).expect("Failed to migrate database");
+            db::migrate_db_v6(&conn).expect("Failed to migrate database");
+            db::migrate_db_v7(&conn).expect("Failed to migrate database");
+            db::migrate_db_v8(&conn).expect("Failed to migrate database");
+            db::migrate_db_v9(&conn).expect("Failed to migrate database");
+            db::migrate_db_v10(&conn).expect("Failed to migrate database");
+            db::migrate_db_v11(&conn).expect("Failed to migrate database");
+            db::migrate_db_v12(&conn).expect("Failed to migrate database");
+            db::migrate_db_v13(&conn).expect("Failed to migrate database");
 
             // Create and manage the database connection state
             let db_connection = db::DbConnection(std::sync::Mutex::new(conn));
             app.manage(db_connection);
+            app.manage(channel_commands::import_registry::ImportRegistry::default());
+            app.manage(channel_commands::vod_commands::EnrichmentRegistry::default());
+            app.manage(channel_commands::epg_commands::EpgImportRegistry::default());
+            channel_commands::auto_refresh::start_auto_refresh(app.handle().clone());
             println!("Database connection managed successfully");
 
             Ok(())
@@ -63,12 +80,35 @@ pub fn run() {
             playlist_commands::get_playlists,
             playlist_commands::delete_playlist,
             playlist_commands::update_playlist,
+            playlist_commands::add_playlist_from_link,
+            account_commands::check_account,
+            account_commands::get_account_status,
             channel_commands::fetch_channels,
             channel_commands::get_selected_channel,
             channel_commands::set_selected_channel,
             channel_commands::category_commands::get_categories,
+            channel_commands::export_m3u::export_m3u,
+            channel_commands::series_commands::get_series_details,
+            channel_commands::vod_commands::get_movie_details,
+            channel_commands::vod_commands::enrich_vod_metadata,
+            channel_commands::vod_commands::cancel_vod_enrichment,
+            channel_commands::epg_commands::import_epg,
+            channel_commands::epg_commands::get_epg_sources,
+            channel_commands::epg_commands::add_epg_source,
+            channel_commands::epg_commands::update_epg_source,
+            channel_commands::epg_commands::delete_epg_source,
+            channel_commands::short_epg::get_short_epg,
+            channel_commands::epg_queries::get_now_next,
+            channel_commands::epg_queries::get_epg_grid,
+            channel_commands::epg_mapping::match_epg_channels,
+            channel_commands::epg_mapping::get_epg_mappings,
+            channel_commands::epg_mapping::set_epg_mapping,
             channel_commands::import_commands::fetch_and_populate_data,
+            channel_commands::import_commands::cancel_import,
             channel_commands::import_commands::process_m3u_content,
+            channel_commands::fetch_api::get_live_streams,
+            channel_commands::fetch_api::get_vod,
+            channel_commands::fetch_api::get_series,
             commands::get_db_path,
             commands::initialize_database,
             commands::add_to_favorites,
@@ -1,5 +1,7 @@
+use serde::ser::SerializeStruct;
 use serde::Deserialize;
 use serde::Serialize;
+use std::collections::BTreeMap;
 use thiserror::Error;
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -13,7 +15,7 @@ pub struct Category {
     pub created_at: Option<String>,
 }
 
-#[derive(Debug, Deserialize, Serialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
 pub struct Channel {
     pub id: Option<i64>,
     pub playlist_id: i64,
@@ -50,6 +52,52 @@ pub struct Channel {
     pub content_type: Option<String>,
     pub authenticated_stream_url: Option<String>,
     pub is_selected: Option<i64>,
+    // Extended M3U attributes
+    pub tvg_name: Option<String>,
+    /// EPG time shift in hours.
+    pub tvg_shift: Option<f64>,
+    pub tvg_language: Option<String>,
+    pub tvg_country: Option<String>,
+    /// Catch-up mode, e.g. `default`, `append` or `shift`.
+    pub catchup: Option<String>,
+    pub catchup_source: Option<String>,
+    /// Headers the player must send, from `#EXTVLCOPT`.
+    pub http_user_agent: Option<String>,
+    pub http_referrer: Option<String>,
+    /// `#KODIPROP` properties, e.g. DRM settings for inputstream.adaptive.
+    pub kodi_props: Option<BTreeMap<String, String>>,
+}
+
+/// Where a playlist's channels come from.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum SourceType {
+    /// An Xtream Codes server; `server_url`, `username` and `password` are used.
+    #[default]
+    Xtream,
+    /// A plain M3U URL stored in `server_url`.
+    M3uUrl,
+    /// A local `.m3u`/`.m3u8` file whose path is stored in `server_url`.
+    M3uFile,
+}
+
+impl SourceType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SourceType::Xtream => "xtream",
+            SourceType::M3uUrl => "m3u_url",
+            SourceType::M3uFile => "m3u_file",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "xtream" => Some(SourceType::Xtream),
+            "m3u_url" => Some(SourceType::M3uUrl),
+            "m3u_file" => Some(SourceType::M3uFile),
+            _ => None,
+        }
+    }
 }
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -64,6 +112,408 @@ pub struct Playlist {
     pub updated_at: Option<String>,
     pub last_updated: Option<String>,
     pub is_active: bool,
+    /// Minutes between background refreshes; 0 disables them.
+    pub refresh_interval_minutes: Option<i64>,
+    pub last_error: Option<String>,
+    /// Preferred stream container, e.g. `ts` or `m3u8`.
+    pub output_format: Option<String>,
+    /// Defaults to `xtream` when a new playlist leaves it out; updates that
+    /// leave it out keep the stored value.
+    pub source_type: Option<SourceType>,
+}
+
+/// Result of `add_playlist_from_link`.
+#[derive(Debug, Serialize)]
+pub struct LinkedPlaylist {
+    pub playlist: Playlist,
+    /// Whether the server answered `player_api.php` for these credentials.
+    pub xtream: bool,
+    /// `None` for plain M3U links, which have no account to probe.
+    pub account: Option<AccountStatus>,
+}
+
+/// Outcome of a single provider request made during an import.
+#[derive(Debug, Serialize, Clone)]
+pub struct EndpointResult {
+    pub endpoint: String,
+    pub success: bool,
+    pub status: Option<u16>,
+    pub item_count: usize,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ImportStatus {
+    #[default]
+    Success,
+    Partial,
+    Failed,
+}
+
+#[derive(Debug, Serialize, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum ImportPhase {
+    EndpointFetched,
+    CategoriesInserted,
+    ChannelsWritten,
+}
+
+/// Payload of the `import-progress` event emitted while a playlist imports.
+#[derive(Debug, Serialize, Clone)]
+pub struct ImportProgress {
+    pub playlist_id: i64,
+    pub phase: ImportPhase,
+    pub current: usize,
+    pub total: usize,
+    pub endpoint: Option<String>,
+}
+
+/// Summary returned to the frontend after a playlist import.
+#[derive(Debug, Serialize, Default)]
+pub struct ImportReport {
+    pub playlist_id: i64,
+    pub status: ImportStatus,
+    pub endpoints: Vec<EndpointResult>,
+    pub categories: usize,
+    pub live: usize,
+    pub vod: usize,
+    pub series: usize,
+    pub inserted: usize,
+    pub updated: usize,
+    pub unchanged: usize,
+    pub removed: usize,
+    pub skipped: usize,
+}
+
+impl ImportReport {
+    /// Derives the overall status from the endpoint outcomes.
+    pub fn finish(&mut self) {
+        let failed = self.endpoints.iter().filter(|e| !e.success).count();
+        self.status = if failed == 0 {
+            ImportStatus::Success
+        } else if failed == self.endpoints.len() {
+            ImportStatus::Failed
+        } else {
+            ImportStatus::Partial
+        };
+    }
+}
+
+/// Problems with a provider account spotted by `check_account`.
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum AccountWarning {
+    /// The server could not be reached or did not answer like an Xtream server.
+    Unreachable,
+    /// The server answered but rejected the username or password.
+    InvalidCredentials,
+    Expired,
+    Banned,
+    Disabled,
+    /// Every allowed connection is already in use.
+    MaxConnections,
+}
+
+/// Latest `player_api.php` account snapshot stored for a playlist.
+#[derive(Debug, Serialize, Clone, Default)]
+pub struct AccountStatus {
+    pub playlist_id: i64,
+    pub reachable: bool,
+    pub authenticated: bool,
+    pub http_status: Option<u16>,
+    pub status: Option<String>,
+    pub message: Option<String>,
+    /// Expiry as a Unix timestamp; `None` for accounts that never expire.
+    pub exp_date: Option<i64>,
+    pub is_trial: bool,
+    pub active_cons: Option<i64>,
+    pub max_connections: Option<i64>,
+    pub allowed_output_formats: Vec<String>,
+    pub server_timezone: Option<String>,
+    pub server_https_port: Option<String>,
+    pub server_timestamp: Option<i64>,
+    pub error: Option<String>,
+    pub checked_at: String,
+    pub warnings: Vec<AccountWarning>,
+}
+
+impl AccountStatus {
+    /// Derives `warnings` from the snapshot, using `now` as a Unix timestamp.
+    pub fn update_warnings(&mut self, now: i64) {
+        let mut warnings = Vec::new();
+        if !self.reachable {
+            warnings.push(AccountWarning::Unreachable);
+        } else if !self.authenticated {
+            warnings.push(AccountWarning::InvalidCredentials);
+        } else {
+            let status = self.status.as_deref().unwrap_or("").to_ascii_lowercase();
+            let expired = matches!(self.exp_date, Some(exp) if exp > 0 && exp <= now);
+            if status == "expired" || expired {
+                warnings.push(AccountWarning::Expired);
+            }
+            if status == "banned" {
+                warnings.push(AccountWarning::Banned);
+            }
+            if status == "disabled" {
+                warnings.push(AccountWarning::Disabled);
+            }
+            if let (Some(active), Some(max)) = (self.active_cons, self.max_connections) {
+                if max > 0 && active >= max {
+                    warnings.push(AccountWarning::MaxConnections);
+                }
+            }
+        }
+        self.warnings = warnings;
+    }
+}
+
+/// A season of a series, cached from `get_series_info`.
+#[derive(Debug, Serialize, Clone, Default)]
+pub struct Season {
+    pub season_number: i64,
+    pub name: Option<String>,
+    pub overview: Option<String>,
+    pub air_date: Option<String>,
+    pub episode_count: Option<i64>,
+    pub cover: Option<String>,
+}
+
+/// A playable episode of a series, cached from `get_series_info`.
+#[derive(Debug, Serialize, Clone, Default)]
+pub struct Episode {
+    pub episode_id: String,
+    pub season_number: Option<i64>,
+    pub episode_num: Option<i64>,
+    pub title: Option<String>,
+    pub duration_secs: Option<i64>,
+    pub duration: Option<String>,
+    pub plot: Option<String>,
+    pub cover: Option<String>,
+    pub rating: Option<f64>,
+    pub release_date: Option<String>,
+    pub container_extension: Option<String>,
+    pub stream_url: String,
+    pub added: Option<String>,
+}
+
+/// Seasons and episodes of one series of a playlist.
+#[derive(Debug, Serialize, Clone, Default)]
+pub struct SeriesDetails {
+    pub playlist_id: i64,
+    pub series_id: String,
+    pub seasons: Vec<Season>,
+    pub episodes: Vec<Episode>,
+    /// RFC 3339 time the details were fetched from the provider.
+    pub fetched_at: String,
+    /// Set when the cache expired but the provider could not be reached.
+    pub stale: bool,
+}
+
+/// A movie with the details cached from `get_vod_info`.
+#[derive(Debug, Serialize, Clone, Default)]
+pub struct MovieDetails {
+    pub playlist_id: i64,
+    pub stream_id: String,
+    pub name: String,
+    pub original_name: Option<String>,
+    pub stream_url: String,
+    pub container_extension: Option<String>,
+    pub plot: Option<String>,
+    pub cast: Option<String>,
+    pub director: Option<String>,
+    pub genre: Option<String>,
+    pub country: Option<String>,
+    pub release_date: Option<String>,
+    pub year: Option<i64>,
+    pub rating: Option<f64>,
+    pub duration_secs: Option<i64>,
+    pub duration: Option<String>,
+    pub imdb_id: Option<String>,
+    pub tmdb_id: Option<String>,
+    pub cover: Option<String>,
+    pub backdrop_path: Vec<String>,
+    /// YouTube video ID of the trailer.
+    pub youtube_trailer: Option<String>,
+    /// Codec details as reported by the panel, e.g. `codec_name`, `width`.
+    pub video: Option<serde_json::Value>,
+    pub audio: Option<serde_json::Value>,
+    pub bitrate: Option<i64>,
+    /// Unix time the details were fetched; `None` if they never were.
+    pub updated_at: Option<i64>,
+}
+
+/// Outcome of `enrich_vod_metadata`.
+#[derive(Debug, Serialize, Clone, Default)]
+pub struct EnrichmentReport {
+    pub playlist_id: i64,
+    pub total: usize,
+    pub enriched: usize,
+    pub failed: usize,
+}
+
+/// Payload of the `vod-enrichment-progress` event emitted after each batch
+/// of movies `enrich_vod_metadata` fetched.
+#[derive(Debug, Serialize, Clone)]
+pub struct EnrichmentProgress {
+    pub playlist_id: i64,
+    pub current: usize,
+    pub total: usize,
+}
+
+/// A programme of `epg_data`. Times are Unix seconds.
+#[derive(Debug, Serialize, Clone, Default)]
+pub struct EpgProgramme {
+    /// The XMLTV id of the channel, which `channels.epg_channel_id` refers to.
+    pub channel_id: String,
+    pub start: i64,
+    pub end: i64,
+    pub title: String,
+    pub sub_title: Option<String>,
+    pub description: Option<String>,
+    pub category: Option<String>,
+    pub icon: Option<String>,
+    pub episode_num: Option<String>,
+    pub season: Option<i64>,
+    pub episode: Option<i64>,
+}
+
+/// The programme airing on a channel and the one after it.
+#[derive(Debug, Serialize, Clone, Default)]
+pub struct NowNext {
+    /// `channels.id`.
+    pub channel_id: i64,
+    pub stream_id: String,
+    pub now: Option<EpgProgramme>,
+    pub next: Option<EpgProgramme>,
+}
+
+/// A row of the EPG grid: a live channel and its programmes in the window.
+#[derive(Debug, Serialize, Clone, Default)]
+pub struct EpgGridChannel {
+    /// `channels.id`.
+    pub channel_id: i64,
+    pub stream_id: String,
+    pub name: String,
+    pub num: Option<String>,
+    pub stream_icon: Option<String>,
+    pub epg_channel_id: Option<String>,
+    pub programmes: Vec<EpgProgramme>,
+}
+
+/// An extra XMLTV guide of a playlist: an http(s) URL, a `file://` URL or
+/// a local path. Plain or gzip-compressed.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct EpgSource {
+    pub id: Option<i64>,
+    pub playlist_id: i64,
+    pub url: String,
+    /// Lower values win when two guides list the same channel.
+    pub priority: i64,
+    pub enabled: bool,
+    pub created_at: Option<String>,
+}
+
+/// Outcome of reading one guide during an EPG import.
+#[derive(Debug, Serialize, Clone, Default)]
+pub struct EpgSourceResult {
+    /// The guide URL with any credentials removed, or the local path.
+    pub source: String,
+    pub success: bool,
+    pub channels: usize,
+    pub programmes: usize,
+    /// Programmes without a channel or with unreadable times.
+    pub skipped: usize,
+    /// Channels left out because a guide of higher priority lists them.
+    pub overridden: usize,
+    pub error: Option<String>,
+}
+
+/// Summary of an XMLTV import into `epg_data`.
+#[derive(Debug, Serialize, Clone, Default)]
+pub struct EpgImportReport {
+    pub playlist_id: i64,
+    /// The guides read, highest priority first.
+    pub sources: Vec<EpgSourceResult>,
+    pub channels: usize,
+    pub programmes: usize,
+    pub skipped: usize,
+    /// Programmes of earlier imports that no guide lists any more.
+    pub removed: usize,
+    /// Channels linked to the guide by name afterwards.
+    pub matched: usize,
+}
+
+/// A channel's guide channel, found by `match_epg_channels` or set by hand.
+#[derive(Debug, Serialize, Clone)]
+pub struct EpgMapping {
+    pub playlist_id: i64,
+    pub stream_id: String,
+    pub epg_channel_id: String,
+    /// Set by hand; kept when channels are matched again.
+    pub manual: bool,
+    /// Similarity of the names for automatic links, 1.0 when equal.
+    pub score: Option<f64>,
+    pub updated_at: String,
+}
+
+/// Outcome of matching a playlist's live channels to its guide.
+#[derive(Debug, Serialize, Clone, Default)]
+pub struct EpgMatchReport {
+    pub playlist_id: i64,
+    pub channels: usize,
+    /// Channels whose own `epg_channel_id` is in the guide.
+    pub linked: usize,
+    pub manual: usize,
+    pub matched: usize,
+    pub unmatched: usize,
+}
+
+/// Playlist input rejected before it is saved.
+#[derive(Debug, Error)]
+pub enum ValidationError {
+    #[error("Server URL is empty")]
+    EmptyServerUrl,
+    #[error("Server URL is not valid: {0}")]
+    InvalidServerUrl(String),
+    #[error("Server URL must use http or https, not {0}")]
+    UnsupportedScheme(String),
+    #[error("File not found: {0}")]
+    FileNotFound(String),
+    #[error("Server could not be reached: {0}")]
+    ServerUnreachable(String),
+    #[error("Server rejected the username or password")]
+    InvalidCredentials,
+    #[error("Only Xtream playlists provide {0}")]
+    NotXtream(&'static str),
+    #[error("Playlist has no EPG URL")]
+    NoEpgSource,
+    #[error("EPG source is not a valid URL or file path: {0}")]
+    InvalidEpgSource(String),
+    #[error("EPG source must be an http or https URL or a local file, not {0}")]
+    UnsupportedEpgScheme(String),
+    #[error("Relay URL must be an http or https URL: {0}")]
+    InvalidRelayUrl(String),
+}
+
+impl ValidationError {
+    /// Stable name of the problem for the frontend, which picks the field
+    /// to highlight by it rather than by the message.
+    pub fn code(&self) -> &'static str {
+        match self {
+            ValidationError::EmptyServerUrl => "empty_server_url",
+            ValidationError::InvalidServerUrl(_) => "invalid_server_url",
+            ValidationError::UnsupportedScheme(_) => "unsupported_scheme",
+            ValidationError::FileNotFound(_) => "file_not_found",
+            ValidationError::ServerUnreachable(_) => "server_unreachable",
+            ValidationError::InvalidCredentials => "invalid_credentials",
+            ValidationError::NotXtream(_) => "not_xtream",
+            ValidationError::NoEpgSource => "no_epg_source",
+            ValidationError::InvalidEpgSource(_) => "invalid_epg_source",
+            ValidationError::UnsupportedEpgScheme(_) => "unsupported_epg_scheme",
+            ValidationError::InvalidRelayUrl(_) => "invalid_relay_url",
+        }
+    }
 }
 
 #[derive(Debug, Error)]
@@ -79,16 +529,61 @@ pub enum Error {
     Url(#[from] url::ParseError),
     #[error("Serde error: {0}")]
     Serde(#[from] serde_json::Error),
+    #[error("XML error: {0}")]
+    Xml(#[from] quick_xml::Error),
+    #[error("Import cancelled")]
+    Cancelled,
+    #[error("Playlist {0} is already being imported")]
+    ImportInProgress(i64),
+    #[error("{0}")]
+    Validation(#[from] ValidationError),
     #[error("Internal error: {0}")]
     #[allow(dead_code)]
     Internal(String),
 }
 
+/// Validation errors reach the frontend as
+/// `{ kind: "validation", code, message }`; everything else as its message.
 impl Serialize for Error {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
         S: serde::Serializer,
     {
-        serializer.serialize_str(&self.to_string())
+        match self {
+            Error::Validation(error) => {
+                let mut state = serializer.serialize_struct("ValidationError", 3)?;
+                state.serialize_field("kind", "validation")?;
+                state.serialize_field("code", error.code())?;
+                state.serialize_field("message", &error.to_string())?;
+                state.end()
+            }
+            _ => serializer.serialize_str(&self.to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn serializes_validation_errors_with_their_code() {
+        let error = Error::from(ValidationError::UnsupportedScheme("ftp".to_string()));
+        assert_eq!(
+            serde_json::to_value(&error).unwrap(),
+            serde_json::json!({
+                "kind": "validation",
+                "code": "unsupported_scheme",
+                "message": "Server URL must use http or https, not ftp",
+            })
+        );
+    }
+
+    #[test]
+    fn serializes_other_errors_as_their_message() {
+        assert_eq!(
+            serde_json::to_value(Error::ImportInProgress(3)).unwrap(),
+            serde_json::json!("Playlist 3 is already being imported")
+        );
     }
 }
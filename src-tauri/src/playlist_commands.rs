@@ -1,16 +1,179 @@
+use crate::account_commands::probe_account;
+use crate::channel_commands::xtream_client::XtreamClient;
 use crate::db::DbConnection;
-use crate::models::{Error, Playlist};
-use rusqlite::params;
+use crate::models::{AccountStatus, Error, LinkedPlaylist, Playlist, SourceType, ValidationError};
+use rusqlite::{params, Connection, Row};
+use std::path::Path;
 use tauri::State;
+use url::Url;
 
-#[tauri::command]
-pub async fn add_playlist(db: State<'_, DbConnection>, playlist: Playlist) -> Result<i64, Error> {
-    println!("Adding playlist: {:?}", playlist);
-    let conn = db.0.lock().unwrap();
+/// Xtream endpoints users tend to paste along with the server address.
+const API_SCRIPTS: [&str; 4] = ["player_api.php", "get.php", "xmltv.php", "panel_api.php"];
+
+/// Turns user input into the base URL of an Xtream server: adds a missing
+/// `http://`, drops any pasted API script, query or fragment and the
+/// trailing slash, e.g. `example.com:8080/player_api.php?username=x`
+/// becomes `http://example.com:8080`.
+pub fn normalize_server_url(input: &str) -> Result<String, ValidationError> {
+    let input = input.trim();
+    if input.is_empty() {
+        return Err(ValidationError::EmptyServerUrl);
+    }
+
+    let with_scheme = if input.contains("://") {
+        input.to_string()
+    } else {
+        format!("http://{}", input)
+    };
+    let mut url = Url::parse(&with_scheme)
+        .map_err(|e| ValidationError::InvalidServerUrl(format!("{} ({})", input, e)))?;
+
+    if url.scheme() != "http" && url.scheme() != "https" {
+        return Err(ValidationError::UnsupportedScheme(url.scheme().to_string()));
+    }
+    if url.host_str().unwrap_or("").is_empty() {
+        return Err(ValidationError::InvalidServerUrl(input.to_string()));
+    }
+
+    let path = url.path().trim_end_matches('/');
+    let path = match path.rsplit_once('/') {
+        Some((parent, script)) if API_SCRIPTS.contains(&script) => parent.to_string(),
+        _ => path.to_string(),
+    };
+    url.set_path(&path);
+    url.set_query(None);
+    url.set_fragment(None);
+
+    Ok(url.as_str().trim_end_matches('/').to_string())
+}
+
+async fn probe_playlist(playlist: &Playlist) -> AccountStatus {
+    let client = XtreamClient::new(&playlist.server_url, &playlist.username, &playlist.password);
+    probe_account(&client, playlist.id.unwrap_or_default()).await
+}
 
+fn require_authenticated(status: &AccountStatus) -> Result<(), ValidationError> {
+    if !status.reachable {
+        let reason = status
+            .error
+            .clone()
+            .unwrap_or_else(|| "no response".to_string());
+        return Err(ValidationError::ServerUnreachable(reason));
+    }
+    if !status.authenticated {
+        return Err(ValidationError::InvalidCredentials);
+    }
+    Ok(())
+}
+
+/// Checks the address of a plain M3U playlist, adding a missing `http://`.
+/// Unlike Xtream server URLs, the path and query are kept.
+pub fn validate_m3u_url(input: &str) -> Result<String, ValidationError> {
+    let input = input.trim();
+    if input.is_empty() {
+        return Err(ValidationError::EmptyServerUrl);
+    }
+
+    let with_scheme = if input.contains("://") {
+        input.to_string()
+    } else {
+        format!("http://{}", input)
+    };
+    let url = Url::parse(&with_scheme)
+        .map_err(|e| ValidationError::InvalidServerUrl(format!("{} ({})", input, e)))?;
+    if url.scheme() != "http" && url.scheme() != "https" {
+        return Err(ValidationError::UnsupportedScheme(url.scheme().to_string()));
+    }
+    Ok(url.to_string())
+}
+
+/// Checks and normalises the source of `playlist`. When `verify` is set,
+/// Xtream servers must also accept the credentials.
+async fn validate_playlist(playlist: &mut Playlist, verify: bool) -> Result<(), Error> {
+    match playlist.source_type.unwrap_or_default() {
+        SourceType::Xtream => {
+            playlist.server_url = normalize_server_url(&playlist.server_url)?;
+            if verify {
+                require_authenticated(&probe_playlist(playlist).await)?;
+            }
+        }
+        SourceType::M3uUrl => {
+            playlist.server_url = validate_m3u_url(&playlist.server_url)?;
+        }
+        SourceType::M3uFile => {
+            let path = playlist.server_url.trim();
+            if path.is_empty() {
+                return Err(ValidationError::EmptyServerUrl.into());
+            }
+            if !Path::new(path).is_file() {
+                return Err(ValidationError::FileNotFound(path.to_string()).into());
+            }
+            playlist.server_url = path.to_string();
+        }
+    }
+    Ok(())
+}
+
+/// Builds a playlist from a provider link such as
+/// `http://host:port/get.php?username=u&password=p&type=m3u_plus&output=ts`
+/// or the equivalent `player_api.php` URL. Links without credentials become
+/// plain M3U URL playlists.
+pub fn parse_playlist_link(link: &str) -> Result<Playlist, ValidationError> {
+    let mut server_url = normalize_server_url(link)?;
+    let link = link.trim();
+    let url = if link.contains("://") {
+        Url::parse(link)
+    } else {
+        Url::parse(&format!("http://{}", link))
+    }
+    .map_err(|e| ValidationError::InvalidServerUrl(format!("{} ({})", link, e)))?;
+
+    let query = |key: &str| {
+        url.query_pairs()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.trim().to_string())
+            .filter(|v| !v.is_empty())
+    };
+    let (source_type, username, password) = match (query("username"), query("password")) {
+        (Some(username), Some(password)) => (SourceType::Xtream, username, password),
+        _ => {
+            server_url = validate_m3u_url(link)?;
+            (SourceType::M3uUrl, String::new(), String::new())
+        }
+    };
+    let output_format = query("output").map(|output| match output.to_ascii_lowercase().as_str() {
+        "hls" | "m3u8" => "m3u8".to_string(),
+        other => other.to_string(),
+    });
+
+    let name = match (url.host_str(), url.port()) {
+        (Some(host), Some(port)) => format!("{}:{}", host, port),
+        (Some(host), None) => host.to_string(),
+        _ => server_url.clone(),
+    };
+
+    Ok(Playlist {
+        id: None,
+        name,
+        server_url,
+        username,
+        password,
+        epg_url: None,
+        created_at: Some(chrono::Utc::now().to_rfc3339()),
+        updated_at: None,
+        last_updated: None,
+        is_active: true,
+        refresh_interval_minutes: None,
+        last_error: None,
+        output_format,
+        source_type: Some(source_type),
+    })
+}
+
+fn insert_playlist(conn: &Connection, playlist: &Playlist) -> Result<i64, Error> {
     let result = conn.execute(
-        "INSERT INTO playlists (name, server_url, username, password, epg_url, created_at, updated_at, last_updated, is_active) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
-        params![playlist.name, playlist.server_url, playlist.username, playlist.password, playlist.epg_url, playlist.created_at, playlist.updated_at, playlist.last_updated, playlist.is_active],
+        "INSERT INTO playlists (name, server_url, username, password, epg_url, created_at, updated_at, last_updated, is_active, refresh_interval_minutes, output_format, source_type) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, COALESCE(?10, 1440), ?11, ?12)",
+        params![playlist.name, playlist.server_url, playlist.username, playlist.password, playlist.epg_url, playlist.created_at, playlist.updated_at, playlist.last_updated, playlist.is_active, playlist.refresh_interval_minutes, playlist.output_format, playlist.source_type.unwrap_or_default().as_str()],
     );
 
     match result {
@@ -26,25 +189,125 @@ pub async fn add_playlist(db: State<'_, DbConnection>, playlist: Playlist) -> Re
     }
 }
 
+/// Columns read by `playlist_from_row`, in order.
+pub const PLAYLIST_COLUMNS: &str = "id, name, server_url, username, password, epg_url, created_at, updated_at, last_updated, is_active, refresh_interval_minutes, last_error, output_format, source_type";
+
+pub fn playlist_from_row(row: &Row) -> rusqlite::Result<Playlist> {
+    Ok(Playlist {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        server_url: row.get(2)?,
+        username: row.get(3)?,
+        password: row.get(4)?,
+        epg_url: row.get(5)?,
+        created_at: row.get(6)?,
+        updated_at: row.get(7)?,
+        last_updated: row.get(8)?,
+        is_active: row.get(9)?,
+        refresh_interval_minutes: row.get(10)?,
+        last_error: row.get(11)?,
+        output_format: row.get(12)?,
+        source_type: Some(SourceType::parse(&row.get::<_, String>(13)?).unwrap_or_default()),
+    })
+}
+
+pub fn load_playlist(conn: &Connection, id: i64) -> Result<Playlist, Error> {
+    let playlist = conn.query_row(
+        &format!("SELECT {} FROM playlists WHERE id = ?1", PLAYLIST_COLUMNS),
+        [id],
+        playlist_from_row,
+    )?;
+    Ok(playlist)
+}
+
+#[tauri::command]
+pub async fn add_playlist(
+    db: State<'_, DbConnection>,
+    mut playlist: Playlist,
+    verify: Option<bool>,
+) -> Result<i64, Error> {
+    validate_playlist(&mut playlist, verify.unwrap_or(false)).await?;
+    println!("Adding playlist: {:?}", playlist);
+    let conn = db.0.lock().unwrap();
+    insert_playlist(&conn, &playlist)
+}
+
+/// Whether the server answered the probe but `player_api.php` is missing or
+/// broken, as opposed to down, overloaded or rejecting the credentials.
+fn lacks_xtream_api(account: &AccountStatus) -> bool {
+    !account.reachable && matches!(account.http_status, Some(status) if status < 500)
+}
+
+/// Stores a link parsed as Xtream as the plain M3U URL it also is.
+fn use_m3u_link(playlist: &mut Playlist, link: &str) -> Result<(), ValidationError> {
+    playlist.server_url = validate_m3u_url(link)?;
+    playlist.username.clear();
+    playlist.password.clear();
+    playlist.source_type = Some(SourceType::M3uUrl);
+    Ok(())
+}
+
+/// Creates a playlist from a pasted provider link, probing `player_api.php`
+/// to find out whether the server speaks the Xtream API. Links to servers
+/// that answer without it are saved as M3U URL playlists, so refreshes do
+/// not try the API first. With `verify`, an Xtream playlist is only saved
+/// if the server accepts the credentials.
+#[tauri::command]
+pub async fn add_playlist_from_link(
+    db: State<'_, DbConnection>,
+    link: String,
+    name: Option<String>,
+    verify: Option<bool>,
+) -> Result<LinkedPlaylist, Error> {
+    let mut playlist = parse_playlist_link(&link)?;
+    if let Some(name) = name.filter(|name| !name.trim().is_empty()) {
+        playlist.name = name.trim().to_string();
+    }
+
+    let mut account = match playlist.source_type {
+        Some(SourceType::Xtream) => Some(probe_playlist(&playlist).await),
+        _ => None,
+    };
+    if let Some(status) = account.as_ref().filter(|status| lacks_xtream_api(status)) {
+        println!(
+            "{} has no usable player_api.php ({}), saving the link as an M3U URL",
+            playlist.server_url,
+            status.error.as_deref().unwrap_or("no account info")
+        );
+        use_m3u_link(&mut playlist, &link)?;
+        account = None;
+    }
+    if let (Some(account), true) = (&account, verify.unwrap_or(false)) {
+        require_authenticated(account)?;
+    }
+    let xtream = matches!(&account, Some(account) if account.authenticated);
+    println!(
+        "Adding playlist from link for {} (xtream: {})",
+        playlist.server_url, xtream
+    );
+
+    let id = {
+        let conn = db.0.lock().unwrap();
+        insert_playlist(&conn, &playlist)?
+    };
+    playlist.id = Some(id);
+    if let Some(account) = account.as_mut() {
+        account.playlist_id = id;
+    }
+
+    Ok(LinkedPlaylist {
+        playlist,
+        xtream,
+        account,
+    })
+}
+
 #[tauri::command]
 pub async fn get_playlists(db: State<'_, DbConnection>) -> Result<Vec<Playlist>, Error> {
     let conn = db.0.lock().unwrap();
-    let mut stmt = conn.prepare("SELECT * FROM playlists")?;
+    let mut stmt = conn.prepare(&format!("SELECT {} FROM playlists", PLAYLIST_COLUMNS))?;
     let playlists = stmt
-        .query_map([], |row| {
-            Ok(Playlist {
-                id: row.get(0)?,
-                name: row.get(1)?,
-                server_url: row.get(2)?,
-                username: row.get(3)?,
-                password: row.get(4)?,
-                epg_url: row.get(5)?,
-                created_at: row.get(6)?,
-                updated_at: row.get(7)?,
-                last_updated: row.get(8)?,
-                is_active: row.get(9)?,
-            })
-        })?
+        .query_map([], playlist_from_row)?
         .collect::<Result<Vec<_>, _>>()?;
 
     Ok(playlists)
@@ -58,11 +321,162 @@ pub async fn delete_playlist(db: State<'_, DbConnection>, id: i64) -> Result<(),
 }
 
 #[tauri::command]
-pub async fn update_playlist(db: State<'_, DbConnection>, playlist: Playlist) -> Result<(), Error> {
+pub async fn update_playlist(
+    db: State<'_, DbConnection>,
+    mut playlist: Playlist,
+    verify: Option<bool>,
+) -> Result<(), Error> {
+    if let (None, Some(id)) = (playlist.source_type, playlist.id) {
+        let conn = db.0.lock().unwrap();
+        playlist.source_type = load_playlist(&conn, id)?.source_type;
+    }
+    validate_playlist(&mut playlist, verify.unwrap_or(false)).await?;
     let conn = db.0.lock().unwrap();
     conn.execute(
-        "UPDATE playlists SET name = ?1, server_url = ?2, username = ?3, password = ?4, epg_url = ?5, updated_at = ?6, last_updated = ?7, is_active = ?8 WHERE id = ?9",
-        params![playlist.name, playlist.server_url, playlist.username, playlist.password, playlist.epg_url, playlist.updated_at, playlist.last_updated, playlist.is_active, playlist.id],
+        "UPDATE playlists SET name = ?1, server_url = ?2, username = ?3, password = ?4, epg_url = ?5, updated_at = ?6, last_updated = ?7, is_active = ?8, refresh_interval_minutes = COALESCE(?9, refresh_interval_minutes), output_format = COALESCE(?10, output_format), source_type = ?11 WHERE id = ?12",
+        params![playlist.name, playlist.server_url, playlist.username, playlist.password, playlist.epg_url, playlist.updated_at, playlist.last_updated, playlist.is_active, playlist.refresh_interval_minutes, playlist.output_format, playlist.source_type.unwrap_or_default().as_str(), playlist.id],
     )?;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalizes_server_urls() {
+        for (input, expected) in [
+            ("http://example.com:8080", "http://example.com:8080"),
+            ("  example.com:8080/ ", "http://example.com:8080"),
+            ("https://example.com/", "https://example.com"),
+            (
+                "example.com:8080/player_api.php?username=u&password=p",
+                "http://example.com:8080",
+            ),
+            (
+                "http://example.com/iptv/get.php#top",
+                "http://example.com/iptv",
+            ),
+            ("http://example.com/iptv/", "http://example.com/iptv"),
+        ] {
+            assert_eq!(normalize_server_url(input).unwrap(), expected, "{}", input);
+        }
+    }
+
+    #[test]
+    fn rejects_bad_server_urls() {
+        assert!(matches!(
+            normalize_server_url("  "),
+            Err(ValidationError::EmptyServerUrl)
+        ));
+        assert!(matches!(
+            normalize_server_url("ftp://example.com"),
+            Err(ValidationError::UnsupportedScheme(scheme)) if scheme == "ftp"
+        ));
+        assert!(matches!(
+            normalize_server_url("http://exa mple.com"),
+            Err(ValidationError::InvalidServerUrl(_))
+        ));
+    }
+
+    #[test]
+    fn validates_m3u_urls_keeping_path_and_query() {
+        assert_eq!(
+            validate_m3u_url(" example.com/lists/tv.m3u?token=abc ").unwrap(),
+            "http://example.com/lists/tv.m3u?token=abc"
+        );
+        assert_eq!(
+            validate_m3u_url("https://example.com/get.php?type=m3u").unwrap(),
+            "https://example.com/get.php?type=m3u"
+        );
+        assert!(matches!(
+            validate_m3u_url(""),
+            Err(ValidationError::EmptyServerUrl)
+        ));
+        assert!(matches!(
+            validate_m3u_url("file:///tmp/tv.m3u"),
+            Err(ValidationError::UnsupportedScheme(scheme)) if scheme == "file"
+        ));
+    }
+
+    #[test]
+    fn parses_xtream_links() {
+        let playlist = parse_playlist_link(
+            "example.com:8080/get.php?username=u&password=p&type=m3u_plus&output=hls",
+        )
+        .unwrap();
+        assert_eq!(playlist.source_type, Some(SourceType::Xtream));
+        assert_eq!(playlist.server_url, "http://example.com:8080");
+        assert_eq!(playlist.name, "example.com:8080");
+        assert_eq!(
+            (playlist.username.as_str(), playlist.password.as_str()),
+            ("u", "p")
+        );
+        assert_eq!(playlist.output_format.as_deref(), Some("m3u8"));
+
+        let playlist =
+            parse_playlist_link("https://example.com/player_api.php?username=u&password=p")
+                .unwrap();
+        assert_eq!(playlist.source_type, Some(SourceType::Xtream));
+        assert_eq!(playlist.server_url, "https://example.com");
+        assert_eq!(playlist.name, "example.com");
+        assert_eq!(playlist.output_format, None);
+
+        let playlist =
+            parse_playlist_link("http://example.com:8080/?username=u&password=p").unwrap();
+        assert_eq!(playlist.source_type, Some(SourceType::Xtream));
+        assert_eq!(playlist.server_url, "http://example.com:8080");
+    }
+
+    #[test]
+    fn parses_links_without_credentials_as_m3u_urls() {
+        let playlist =
+            parse_playlist_link("http://example.com:8080/get.php?type=m3u_plus&output=ts").unwrap();
+        assert_eq!(playlist.source_type, Some(SourceType::M3uUrl));
+        assert_eq!(
+            playlist.server_url,
+            "http://example.com:8080/get.php?type=m3u_plus&output=ts"
+        );
+        assert_eq!(
+            (playlist.username.as_str(), playlist.password.as_str()),
+            ("", "")
+        );
+        assert_eq!(playlist.output_format.as_deref(), Some("ts"));
+
+        // Both credentials are needed for an Xtream playlist
+        let playlist = parse_playlist_link("example.com/get.php?username=u&password=").unwrap();
+        assert_eq!(playlist.source_type, Some(SourceType::M3uUrl));
+        assert_eq!(
+            playlist.server_url,
+            "http://example.com/get.php?username=u&password="
+        );
+    }
+
+    #[test]
+    fn saves_links_to_servers_without_the_api_as_m3u_urls() {
+        let link = "example.com:8080/get.php?username=u&password=p&type=m3u_plus";
+        let account = |reachable, http_status| AccountStatus {
+            reachable,
+            http_status,
+            ..Default::default()
+        };
+        assert!(lacks_xtream_api(&account(false, Some(404))));
+        assert!(!lacks_xtream_api(&account(true, Some(200))));
+        assert!(!lacks_xtream_api(&account(true, Some(401))));
+        assert!(!lacks_xtream_api(&account(false, Some(503))));
+        assert!(!lacks_xtream_api(&account(false, None)));
+
+        let mut playlist = parse_playlist_link(link).unwrap();
+        use_m3u_link(&mut playlist, link).unwrap();
+        assert_eq!(playlist.source_type, Some(SourceType::M3uUrl));
+        assert_eq!(
+            playlist.server_url,
+            "http://example.com:8080/get.php?username=u&password=p&type=m3u_plus"
+        );
+        assert_eq!(
+            (playlist.username.as_str(), playlist.password.as_str()),
+            ("", "")
+        );
+        assert_eq!(playlist.name, "example.com:8080");
+    }
+}
@@ -1,4 +1,7 @@
 import { invoke } from "@tauri-apps/api/core";
+import { listen, type UnlistenFn } from "@tauri-apps/api/event";
+
+export type SourceType = 'xtream' | 'm3u_url' | 'm3u_file';
 
 export interface Playlist {
     id?: number;
@@ -11,22 +14,53 @@ export interface Playlist {
     updated_at?: string;
     last_updated?: string;
     is_active: boolean;
+    refresh_interval_minutes?: number;
+    last_error?: string;
+    // Preferred live format, 'ts' or 'm3u8'; ignored if the account does not allow it.
+    output_format?: string;
+    // For m3u_url and m3u_file playlists, server_url holds the URL or file path.
+    source_type?: SourceType;
+}
+
+// Rejected playlist input; other command errors arrive as plain strings.
+export interface ValidationError {
+    kind: 'validation';
+    code: 'empty_server_url' | 'invalid_server_url' | 'unsupported_scheme' | 'file_not_found'
+        | 'server_unreachable' | 'invalid_credentials' | 'not_xtream' | 'no_epg_source'
+        | 'invalid_epg_source' | 'unsupported_epg_scheme' | 'invalid_relay_url';
+    message: string;
+}
+
+export function isValidationError(error: unknown): error is ValidationError {
+    return typeof error === 'object' && error !== null && (error as ValidationError).kind === 'validation';
 }
 
 export async function initializeDatabase(): Promise<void> {
     await invoke('initialize_database');
 }
 
-export async function addPlaylist(playlist: Playlist): Promise<number> {
-    return await invoke('add_playlist', { playlist });
+// With `verify`, the server is asked to accept the credentials before saving.
+export async function addPlaylist(playlist: Playlist, verify = false): Promise<number> {
+    return await invoke('add_playlist', { playlist, verify });
+}
+
+export interface LinkedPlaylist {
+    playlist: Playlist;
+    xtream: boolean;
+    account?: AccountStatus;
+}
+
+// Creates a playlist from a provider link such as get.php?username=...&password=...
+export async function addPlaylistFromLink(link: string, name?: string, verify = false): Promise<LinkedPlaylist> {
+    return await invoke('add_playlist_from_link', { link, name, verify });
 }
 
 export async function getPlaylists(): Promise<Playlist[]> {
     return await invoke('get_playlists');
 }
 
-export async function updatePlaylist(id: number, playlist: Playlist): Promise<void> {
-    return await invoke('update_playlist', { id, playlist });
+export async function updatePlaylist(id: number, playlist: Playlist, verify = false): Promise<void> {
+    return await invoke('update_playlist', { id, playlist, verify });
 }
 
 export async function deletePlaylist(id: number): Promise<void> {
@@ -73,6 +107,311 @@ export interface Channel {
     content_type?: string;
     authenticated_stream_url?: string;
     is_selected?: number;
+    tvg_name?: string;
+    tvg_shift?: number;
+    tvg_language?: string;
+    tvg_country?: string;
+    catchup?: string;
+    catchup_source?: string;
+    http_user_agent?: string;
+    http_referrer?: string;
+    kodi_props?: Record<string, string>;
+}
+
+export interface Category {
+    id?: number;
+    playlist_id: number;
+    category_id: string;
+    name: string;
+    content_type: 'live' | 'movie' | 'series';
+    parent_id?: number;
+    created_at?: string;
+}
+
+// For M3U playlists, categories are the file's groups with generated IDs.
+export async function getCategories(playlistId: number, contentType?: string): Promise<Category[]> {
+    return await invoke('get_categories', { playlistId, contentType });
+}
+
+export interface ExportFilter {
+    playlist_id?: number;
+    category_id?: string;
+    content_type?: string;
+    favorites_only?: boolean;
+    query?: string;
+}
+
+// Returns the extended M3U and also writes it to `path` when given. With a
+// relay URL, streams point at `{relayUrl}/{playlist_id}/{stream_id}` so the
+// file carries no provider credentials.
+export async function exportM3u(filter: ExportFilter, path?: string, relayUrl?: string): Promise<string> {
+    return await invoke('export_m3u', { filter, path, relayUrl });
+}
+
+export interface Season {
+    season_number: number;
+    name?: string;
+    overview?: string;
+    air_date?: string;
+    episode_count?: number;
+    cover?: string;
+}
+
+export interface Episode {
+    episode_id: string;
+    season_number?: number;
+    episode_num?: number;
+    title?: string;
+    duration_secs?: number;
+    duration?: string;
+    plot?: string;
+    cover?: string;
+    rating?: number;
+    release_date?: string;
+    container_extension?: string;
+    stream_url: string;
+    added?: string;
+}
+
+export interface SeriesDetails {
+    playlist_id: number;
+    series_id: string;
+    seasons: Season[];
+    episodes: Episode[];
+    fetched_at: string;
+    // True when the cache expired and the provider could not be reached.
+    stale: boolean;
+}
+
+// `seriesId` is the stream_id of a series channel. Results are cached for a day.
+export async function getSeriesDetails(playlistId: number, seriesId: string, forceRefresh = false): Promise<SeriesDetails> {
+    return await invoke('get_series_details', { playlistId, seriesId, forceRefresh });
+}
+
+export interface MovieDetails {
+    playlist_id: number;
+    stream_id: string;
+    name: string;
+    original_name?: string;
+    stream_url: string;
+    container_extension?: string;
+    plot?: string;
+    cast?: string;
+    director?: string;
+    genre?: string;
+    country?: string;
+    release_date?: string;
+    year?: number;
+    // Out of 10
+    rating?: number;
+    duration_secs?: number;
+    duration?: string;
+    imdb_id?: string;
+    tmdb_id?: string;
+    cover?: string;
+    backdrop_path: string[];
+    youtube_trailer?: string;
+    video?: Record<string, unknown>;
+    audio?: Record<string, unknown>;
+    bitrate?: number;
+    updated_at?: number;
+}
+
+export async function getMovieDetails(playlistId: number, streamId: string, forceRefresh = false): Promise<MovieDetails> {
+    return await invoke('get_movie_details', { playlistId, streamId, forceRefresh });
+}
+
+export interface EnrichmentReport {
+    playlist_id: number;
+    total: number;
+    enriched: number;
+    failed: number;
+}
+
+export interface EnrichmentProgress {
+    playlist_id: number;
+    current: number;
+    total: number;
+}
+
+// Fetches details for every movie not enriched yet. It can take minutes on
+// large catalogues; follow onEnrichmentProgress rather than awaiting it.
+export async function enrichVodMetadata(playlistId: number): Promise<EnrichmentReport> {
+    return await invoke('enrich_vod_metadata', { playlistId });
+}
+
+export async function cancelVodEnrichment(playlistId: number): Promise<boolean> {
+    return await invoke('cancel_vod_enrichment', { playlistId });
+}
+
+export async function onEnrichmentProgress(callback: (progress: EnrichmentProgress) => void): Promise<UnlistenFn> {
+    return await listen<EnrichmentProgress>('vod-enrichment-progress', event => callback(event.payload));
+}
+
+export interface EpgSource {
+    id?: number;
+    playlist_id: number;
+    url: string;
+    priority: number;
+    enabled: boolean;
+    created_at?: string;
+}
+
+export interface EpgSourceResult {
+    source: string;
+    success: boolean;
+    channels: number;
+    programmes: number;
+    skipped: number;
+    overridden: number;
+    error?: string;
+}
+
+export interface EpgImportReport {
+    playlist_id: number;
+    sources: EpgSourceResult[];
+    channels: number;
+    programmes: number;
+    skipped: number;
+    removed: number;
+    matched: number;
+}
+
+// Imports the playlist's extra EPG sources by priority, then its epg_url or
+// xmltv.php for Xtream. Sources may be http(s), file:// or local paths, gzipped or not.
+export async function importEpg(playlistId: number): Promise<EpgImportReport> {
+    return await invoke('import_epg', { playlistId });
+}
+
+export async function getEpgSources(playlistId: number): Promise<EpgSource[]> {
+    return await invoke('get_epg_sources', { playlistId });
+}
+
+export async function addEpgSource(playlistId: number, url: string, priority?: number): Promise<EpgSource> {
+    return await invoke('add_epg_source', { playlistId, url, priority });
+}
+
+export async function updateEpgSource(source: EpgSource): Promise<void> {
+    return await invoke('update_epg_source', { source });
+}
+
+export async function deleteEpgSource(id: number): Promise<void> {
+    return await invoke('delete_epg_source', { id });
+}
+
+export interface EpgProgramme {
+    channel_id: string;
+    start: number;
+    end: number;
+    title: string;
+    sub_title?: string;
+    description?: string;
+    category?: string;
+    icon?: string;
+    episode_num?: string;
+    season?: number;
+    episode?: number;
+}
+
+// Fetches one Xtream live stream's listings with get_short_epg, or the whole
+// get_simple_data_table when fullDay is set, and caches them in the EPG.
+export async function getShortEpg(playlistId: number, streamId: string, limit?: number, fullDay?: boolean): Promise<EpgProgramme[]> {
+    return await invoke('get_short_epg', { playlistId, streamId, limit, fullDay });
+}
+
+export interface NowNext {
+    channel_id: number;
+    stream_id: string;
+    now?: EpgProgramme;
+    next?: EpgProgramme;
+}
+
+export interface EpgGridChannel {
+    channel_id: number;
+    stream_id: string;
+    name: string;
+    num?: string;
+    stream_icon?: string;
+    epg_channel_id?: string;
+    programmes: EpgProgramme[];
+}
+
+// Current and next programme of each channel id, in the order given.
+export async function getNowNext(playlistId: number, channelIds: number[]): Promise<NowNext[]> {
+    return await invoke('get_now_next', { playlistId, channelIds });
+}
+
+// Live channels of a category (all when omitted) with their programmes
+// between from and to, in Unix seconds.
+export async function getEpgGrid(playlistId: number, from: number, to: number, category?: string): Promise<EpgGridChannel[]> {
+    return await invoke('get_epg_grid', { playlistId, category, from, to });
+}
+
+export interface EpgMapping {
+    playlist_id: number;
+    stream_id: string;
+    epg_channel_id: string;
+    manual: boolean;
+    score?: number;
+    updated_at: string;
+}
+
+export interface EpgMatchReport {
+    playlist_id: number;
+    channels: number;
+    linked: number;
+    manual: number;
+    matched: number;
+    unmatched: number;
+}
+
+// Links channels without a usable tvg-id to guide channels by name. Also runs after importEpg.
+export async function matchEpgChannels(playlistId: number): Promise<EpgMatchReport> {
+    return await invoke('match_epg_channels', { playlistId });
+}
+
+export async function getEpgMappings(playlistId: number): Promise<EpgMapping[]> {
+    return await invoke('get_epg_mappings', { playlistId });
+}
+
+// Sets a channel's guide channel by hand; null hands it back to automatic matching.
+export async function setEpgMapping(playlistId: number, streamId: string, epgChannelId: string | null): Promise<void> {
+    return await invoke('set_epg_mapping', { playlistId, streamId, epgChannelId });
+}
+
+export interface EndpointResult {
+    endpoint: string;
+    success: boolean;
+    status?: number;
+    item_count: number;
+    error?: string;
+}
+
+export interface ImportReport {
+    playlist_id: number;
+    status: 'success' | 'partial' | 'failed';
+    endpoints: EndpointResult[];
+    categories: number;
+    live: number;
+    vod: number;
+    series: number;
+    inserted: number;
+    updated: number;
+    unchanged: number;
+    removed: number;
+    skipped: number;
+}
+
+export interface ImportProgress {
+    playlist_id: number;
+    phase: 'endpoint_fetched' | 'categories_inserted' | 'channels_written';
+    current: number;
+    total: number;
+    endpoint?: string;
+}
+
+export async function onImportProgress(callback: (progress: ImportProgress) => void): Promise<UnlistenFn> {
+    return await listen<ImportProgress>('import-progress', event => callback(event.payload));
 }
 
 export async function fetchChannels(id: number): Promise<Channel[]> {
@@ -89,19 +428,16 @@ export async function fetchChannels(id: number): Promise<Channel[]> {
         if (playlist) {
             try {
                 console.log(`Fetching data for playlist: ${playlist.name}`);
-                // Clean up the server URL to ensure it doesn't have trailing slashes
-                let serverUrl = playlist.server_url.trim();
-                if (serverUrl.endsWith('/')) {
-                    serverUrl = serverUrl.slice(0, -1);
-                }
-
-                console.log(`Attempting to fetch data from: ${serverUrl}`);
-                await invoke('fetch_and_populate_data', {
-                    playlistId: id,
-                    serverUrl: serverUrl,
-                    username: playlist.username,
-                    password: playlist.password
+                const report: ImportReport = await invoke('fetch_and_populate_data', {
+                    playlistId: id
                 });
+                console.log('Import report:', report);
+                if (report.status === 'failed') {
+                    const errors = report.endpoints
+                        .map(e => `${e.endpoint}: ${e.error ?? 'unknown error'}`)
+                        .join('\n');
+                    throw new Error(`Import failed for every endpoint:\n${errors}`);
+                }
 
                 // Now fetch the channels again
                 return await invoke('fetch_channels', { playlistId: id });
@@ -115,6 +451,46 @@ export async function fetchChannels(id: number): Promise<Channel[]> {
     return channels;
 }
 
+export async function cancelImport(playlistId: number): Promise<boolean> {
+    return await invoke('cancel_import', { playlistId });
+}
+
+export type AccountWarning =
+    | 'unreachable'
+    | 'invalid_credentials'
+    | 'expired'
+    | 'banned'
+    | 'disabled'
+    | 'max_connections';
+
+export interface AccountStatus {
+    playlist_id: number;
+    reachable: boolean;
+    authenticated: boolean;
+    http_status?: number;
+    status?: string;
+    message?: string;
+    exp_date?: number;
+    is_trial: boolean;
+    active_cons?: number;
+    max_connections?: number;
+    allowed_output_formats: string[];
+    server_timezone?: string;
+    server_https_port?: string;
+    server_timestamp?: number;
+    error?: string;
+    checked_at: string;
+    warnings: AccountWarning[];
+}
+
+export async function checkAccount(playlistId: number): Promise<AccountStatus> {
+    return await invoke('check_account', { playlistId });
+}
+
+export async function getAccountStatus(playlistId: number): Promise<AccountStatus | null> {
+    return await invoke('get_account_status', { playlistId });
+}
+
 export async function setSelectedChannel(playlist_id: number, channel_id: number): Promise<void> {
     return await invoke('set_selected_channel', { channel_id });
 }
@@ -127,10 +503,11 @@ export async function addToFavorites(playlistId: number, streamId: string, conte
     return await invoke('add_to_favorites', { playlistId, streamId, contentType });
 }
 
-export async function removeFromFavorites(playlistId: number, streamId: string): Promise<void> {
-    return await invoke('remove_from_favorites', { playlistId, streamId });
+export async function removeFromFavorites(playlistId: number, streamId: string, contentType: string): Promise<void> {
+    return await invoke('remove_from_favorites', { playlistId, streamId, contentType });
 }
 
-export async function getFavorites(): Promise<Array<[number, string]>> {
+// [playlist_id, stream_id, content_type]
+export async function getFavorites(): Promise<Array<[number, string, string]>> {
     return await invoke('get_favorites');
 }